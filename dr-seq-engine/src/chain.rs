@@ -0,0 +1,128 @@
+//! Chain of patterns for song mode.
+
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of entries in a chain.
+pub const MAX_CHAIN_ENTRIES: usize = 16;
+
+/// Entry of a chain, playing a pattern for a number of bars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainEntry {
+    /// Number of the pattern, starting at 0.
+    pub pattern: u32,
+
+    /// Number of bars the pattern is played.
+    pub repeats: u32,
+
+    /// Fill mode for trigger conditions while the entry is played.
+    pub fill: bool,
+}
+
+impl Default for ChainEntry {
+    fn default() -> Self {
+        Self {
+            pattern: 0,
+            repeats: 1,
+            fill: false,
+        }
+    }
+}
+
+/// Position within a chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainPosition {
+    /// Index of the entry.
+    pub index: usize,
+
+    /// Repetition within the entry, starting at 0.
+    pub repeat: u32,
+
+    /// The entry itself.
+    pub entry: ChainEntry,
+}
+
+/// Chain of patterns with repeat counts.
+///
+/// The position is derived from the bar number only, so playback is deterministic
+/// with respect to the song position of the host.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chain {
+    /// Entries of the chain.
+    entries: Vec<ChainEntry, MAX_CHAIN_ENTRIES>,
+
+    /// Flag if the chain starts over after the last entry.
+    looping: bool,
+}
+
+impl Chain {
+    /// Returns a new empty instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends an entry.
+    ///
+    /// Returns the entry back as error if the chain already holds `MAX_CHAIN_ENTRIES` entries.
+    pub fn push(&mut self, entry: ChainEntry) -> Result<(), ChainEntry> {
+        self.entries.push(entry)
+    }
+
+    /// Removes all entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Returns a reference to the entries.
+    pub fn entries(&self) -> &[ChainEntry] {
+        &self.entries
+    }
+
+    /// Sets if the chain starts over after the last entry.
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// Returns if the chain starts over after the last entry.
+    pub fn looping(&self) -> bool {
+        self.looping
+    }
+
+    /// Returns the total length of the chain in bars.
+    pub fn bars(&self) -> u32 {
+        self.entries
+            .iter()
+            .fold(0u32, |bars, entry| bars.saturating_add(entry.repeats))
+    }
+
+    /// Returns the position in the chain at a bar number, counted from the start of the song.
+    ///
+    /// After the last entry, the chain either starts over or stays on the last bar of the
+    /// last entry. Returns `None` if the chain has no bars at all.
+    pub fn position(&self, bar_no: u32) -> Option<ChainPosition> {
+        let bars = self.bars();
+
+        if bars == 0 {
+            return None;
+        }
+
+        let mut bar_no = if self.looping {
+            bar_no % bars
+        } else {
+            bar_no.min(bars - 1)
+        };
+
+        for (index, entry) in self.entries.iter().enumerate() {
+            if bar_no < entry.repeats {
+                return Some(ChainPosition {
+                    index,
+                    repeat: bar_no,
+                    entry: *entry,
+                });
+            }
+            bar_no -= entry.repeats;
+        }
+
+        None
+    }
+}
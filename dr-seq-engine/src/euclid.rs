@@ -0,0 +1,205 @@
+//! Euclidean rhythm generator.
+
+use serde::{Deserialize, Serialize};
+
+use crate::pattern::Pattern;
+use crate::step::StepEvent;
+
+/// Euclidean rhythm, distributing a number of pulses as evenly as possible over
+/// a number of steps using the Bjorklund algorithm.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Euclid {
+    /// Number of hits.
+    pub pulses: u32,
+
+    /// Length of the rhythm in steps.
+    pub steps: u32,
+
+    /// Number of steps the rhythm is rotated to the right.
+    pub rotation: i32,
+}
+
+impl Euclid {
+    /// Returns a new instance.
+    pub fn new(pulses: u32, steps: u32, rotation: i32) -> Self {
+        Self {
+            pulses,
+            steps,
+            rotation,
+        }
+    }
+
+    /// Returns the hits of the rhythm for a capacity of `N` steps.
+    ///
+    /// The rhythm starts with a hit before the rotation is applied. Steps beyond
+    /// the length of the rhythm are never hits. The length is limited to `N` and
+    /// the number of pulses to the length.
+    pub fn hits<const N: usize>(&self) -> [bool; N] {
+        let steps = (self.steps as usize).min(N);
+        let pulses = (self.pulses as usize).min(steps);
+
+        let mut hits = [false; N];
+
+        if pulses == 0 {
+            return hits;
+        }
+
+        let mut rhythm = [false; N];
+        let mut len = 0;
+
+        if pulses == steps {
+            rhythm[..steps].fill(true);
+        } else {
+            // Bjorklund algorithm: the remainders of a euclidean division are
+            // distributed recursively over the groups of the previous level.
+            let mut counts = [0; N];
+            let mut remainders = [0; N];
+            let mut divisor = steps - pulses;
+            let mut level = 0;
+
+            remainders[0] = pulses;
+
+            loop {
+                counts[level] = divisor / remainders[level];
+                remainders[level + 1] = divisor % remainders[level];
+                divisor = remainders[level];
+                level += 1;
+
+                if remainders[level] <= 1 {
+                    break;
+                }
+            }
+
+            counts[level] = divisor;
+
+            build(level as isize, &counts, &remainders, &mut rhythm, &mut len);
+        }
+
+        // The rhythm is rotated to start with a hit and then by the rotation setting.
+        let start = rhythm[..steps].iter().position(|hit| *hit).unwrap_or(0);
+        let rotation = self.rotation.rem_euclid(steps as i32) as usize;
+
+        for (n, hit) in hits[..steps].iter_mut().enumerate() {
+            *hit = rhythm[(n + start + steps - rotation) % steps];
+        }
+
+        hits
+    }
+
+    /// Fills the steps of a pattern with the rhythm.
+    ///
+    /// Steps with a hit are enabled and play `event`, all other steps are disabled.
+    /// The length of the pattern is set to the length of the rhythm, other settings
+    /// of the steps are kept.
+    pub fn fill<const N: usize>(&self, pattern: &mut Pattern<N>, event: &StepEvent) {
+        let hits = self.hits::<N>();
+
+        for (step, hit) in pattern.steps_mut().iter_mut().zip(hits) {
+            if hit {
+                step.enable();
+                step.set_event(Some(event.clone()));
+            } else {
+                step.disable();
+            }
+        }
+
+        pattern.set_length(self.steps);
+    }
+}
+
+/// Appends the groups of a level of the Bjorklund algorithm to `rhythm`.
+///
+/// Level -1 is a single rest and level -2 a single hit.
+fn build(
+    level: isize,
+    counts: &[usize],
+    remainders: &[usize],
+    rhythm: &mut [bool],
+    len: &mut usize,
+) {
+    match level {
+        -1 | -2 => {
+            if let Some(step) = rhythm.get_mut(*len) {
+                *step = level == -2;
+                *len += 1;
+            }
+        }
+        _ => {
+            let index = level as usize;
+
+            for _ in 0..counts[index] {
+                build(level - 1, counts, remainders, rhythm, len);
+            }
+
+            if remainders[index] != 0 {
+                build(level - 2, counts, remainders, rhythm, len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::{Pitch, Velocity};
+
+    /// Returns the hits of a rhythm as a string of `x` for hits and `.` for rests.
+    fn rhythm<const N: usize>(pulses: u32, steps: u32, rotation: i32) -> String {
+        Euclid::new(pulses, steps, rotation)
+            .hits::<N>()
+            .iter()
+            .map(|hit| if *hit { 'x' } else { '.' })
+            .collect()
+    }
+
+    #[test]
+    fn known_rhythms() {
+        assert_eq!(rhythm::<8>(3, 8, 0), "x..x..x.");
+        assert_eq!(rhythm::<8>(5, 8, 0), "x.xx.xx.");
+        assert_eq!(rhythm::<16>(4, 16, 0), "x...x...x...x...");
+    }
+
+    #[test]
+    fn rotation_moves_to_the_right() {
+        assert_eq!(rhythm::<8>(3, 8, 1), ".x..x..x");
+        assert_eq!(rhythm::<8>(3, 8, 9), ".x..x..x");
+        assert_eq!(rhythm::<8>(3, 8, 8), "x..x..x.");
+        assert_eq!(rhythm::<8>(3, 8, -1), "..x..x.x");
+    }
+
+    #[test]
+    fn edge_cases() {
+        assert_eq!(rhythm::<8>(0, 8, 3), "........");
+        assert_eq!(rhythm::<8>(8, 8, 3), "xxxxxxxx");
+        assert_eq!(rhythm::<8>(12, 8, 0), "xxxxxxxx");
+        assert_eq!(rhythm::<8>(4, 0, 0), "........");
+
+        // Steps beyond the length of the rhythm are never hits.
+        assert_eq!(rhythm::<8>(2, 4, 0), "x.x.....");
+
+        // The length is limited to the capacity.
+        assert_eq!(rhythm::<4>(3, 8, 0), "xxx.");
+    }
+
+    #[test]
+    fn fill_short_rhythm() {
+        let event = StepEvent::NoteOn {
+            pitch: Pitch::Default,
+            vel: Velocity::Default,
+        };
+        let mut pattern = Pattern::<16>::new();
+        for step in pattern.steps_mut() {
+            step.enable();
+            step.set_probability(50);
+        }
+
+        Euclid::new(3, 8, 0).fill(&mut pattern, &event);
+
+        assert_eq!(pattern.length(), 8);
+        for (n, step) in pattern.steps().iter().enumerate() {
+            assert_eq!(step.enabled(), [0, 3, 6].contains(&n), "step {n}");
+            assert_eq!(step.probability(), 50);
+        }
+        assert_eq!(pattern.step(3).event(), Some(&event));
+    }
+}
@@ -1,12 +1,28 @@
 #![doc = include_str!("../README.md")]
 #![cfg_attr(not(test), no_std)]
 
+mod chain;
+mod euclid;
+mod notation;
 mod params;
 mod pattern;
+mod render;
+mod rng;
+mod scale;
+mod scheduler;
+mod sequencer;
 mod step;
 mod track;
 
+pub use chain::*;
+pub use euclid::*;
+pub use notation::*;
 pub use params::*;
 pub use pattern::*;
+pub use render::*;
+pub use rng::*;
+pub use scale::*;
+pub use scheduler::*;
+pub use sequencer::*;
 pub use step::*;
 pub use track::*;
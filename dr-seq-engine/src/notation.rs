@@ -0,0 +1,871 @@
+//! Text notation for patterns.
+//!
+//! The notation describes a set of patterns, one per track, in a line-based format
+//! that is easy to read, to diff and to paste:
+//!
+//! ```text
+//! dr-seq 2
+//! # Basic beat
+//! track 1 length=16
+//! steps X___X___X___X___
+//! track 2 gate=100
+//! steps ____A_______X__x
+//! step 16 prob=50 cond=1:2
+//! track 3 delay=-4
+//! steps X_X_V_X_X_X_X_X_
+//! step 5 vel=96 ratchets=3 ramp=up
+//! ```
+//!
+//! The first line holds the version of the notation. Version 2 added the `choke` and
+//! `accent` settings, older versions are still read. Empty lines and text after `#`
+//! are ignored. Each `track` line starts the description of a track, numbered from 1,
+//! with optional settings:
+//! - `length`: Active length in steps.
+//! - `enable`: Playback `on` or `off`.
+//! - `delay`: Time delay in pulses.
+//! - `gate`: Note length in percent of a step.
+//! - `choke`: Number of the choke group, counted from 1, or 0 for none.
+//! - `accent`: Accent velocity of the accent lane of the track.
+//!
+//! The `steps` line holds one character per step:
+//! - `_`: Off.
+//! - `X`: Normal velocity.
+//! - `A`: Accent.
+//! - `x`: Weak.
+//! - `.`: Ghost.
+//! - `V`: Individual velocity, set with `vel` on the `step` line.
+//! - `o`: Enabled without a note.
+//!
+//! A `step` line after the `steps` line, followed by the step number counted from 1,
+//! holds the settings of a single step:
+//! - `vel`: Individual velocity of the note in the range of 0-127.
+//! - `pitch`: Note number instead of the default note of the track.
+//! - `layers`: Comma-separated note numbers played together with the note. A number can be
+//!   followed by `@` and a velocity character or value, if it differs from the note.
+//!   The default note of the track is written as `*`.
+//! - `prob`: Trigger probability in percent.
+//! - `cond`: Trigger condition, `always`, `1st`, `!1st`, `fill`, `!fill` or a ratio like `1:4`.
+//! - `gate`: Note length in percent of a step or `tie`.
+//! - `nudge`: Timing offset in pulses.
+//! - `ratchets`: Number of hits.
+//! - `ramp`: Velocity ramp of the hits, `flat`, `up` or `down`.
+//! - `accent`: Step set on the accent lane of the track, `on` or `off`.
+//!
+//! Tracks and settings that are omitted keep their default values. Note offs and the
+//! notes of disabled steps are not part of the notation.
+
+use core::fmt;
+
+use crate::params::{Condition, Gate, Pitch, Ramp, Velocity};
+use crate::pattern::Pattern;
+use crate::sequencer::TrackSettings;
+use crate::step::{MAX_RATCHETS, Step, StepEvent};
+
+/// Version of the notation written by this implementation.
+pub const NOTATION_VERSION: u32 = 2;
+
+/// Keyword of the first line.
+const HEADER: &str = "dr-seq";
+
+/// Patterns and settings of all tracks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternSet<const NUM_TRACKS: usize, const NUM_STEPS: usize> {
+    /// Patterns of the tracks.
+    pub patterns: [Pattern<NUM_STEPS>; NUM_TRACKS],
+
+    /// Settings of the tracks.
+    pub tracks: [TrackSettings; NUM_TRACKS],
+}
+
+impl<const NUM_TRACKS: usize, const NUM_STEPS: usize> Default
+    for PatternSet<NUM_TRACKS, NUM_STEPS>
+{
+    fn default() -> Self {
+        Self {
+            patterns: core::array::from_fn(|_| Pattern::new()),
+            tracks: [TrackSettings::default(); NUM_TRACKS],
+        }
+    }
+}
+
+impl<const NUM_TRACKS: usize, const NUM_STEPS: usize> PatternSet<NUM_TRACKS, NUM_STEPS> {
+    /// Returns a new instance with empty patterns.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the patterns and settings described by `text`.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut set = Self::new();
+        let mut header = false;
+        let mut track = None;
+
+        for (line_index, line) in text.lines().enumerate() {
+            let line_no = line_index + 1;
+            let content = line.split('#').next().unwrap_or_default();
+            let mut tokens = Tokens::new(content, line_no);
+
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+
+            if !header {
+                if keyword.text != HEADER {
+                    return Err(keyword.error(ParseErrorKind::MissingHeader));
+                }
+                let version = tokens.expect()?;
+                match version.text.parse::<u32>() {
+                    Ok(1..=NOTATION_VERSION) => {}
+                    Ok(_) => return Err(version.error(ParseErrorKind::UnsupportedVersion)),
+                    Err(_) => return Err(version.error(ParseErrorKind::InvalidValue)),
+                }
+                tokens.finish()?;
+                header = true;
+                continue;
+            }
+
+            match keyword.text {
+                "track" => {
+                    let number = tokens.expect()?;
+                    let t = number.number(1, NUM_TRACKS)? - 1;
+                    set.parse_track_settings(t, &mut tokens)?;
+                    track = Some(t);
+                }
+                "steps" => {
+                    let t = track.ok_or(keyword.error(ParseErrorKind::MissingTrack))?;
+                    let steps = tokens.expect()?;
+                    set.parse_steps(t, &steps)?;
+                    tokens.finish()?;
+                }
+                "step" => {
+                    let t = track.ok_or(keyword.error(ParseErrorKind::MissingTrack))?;
+                    let number = tokens.expect()?;
+                    let s = number.number(1, NUM_STEPS)? - 1;
+                    parse_step_settings(&mut set.patterns[t].steps_mut()[s], &mut tokens)?;
+                }
+                _ => return Err(keyword.error(ParseErrorKind::UnknownKeyword)),
+            }
+        }
+
+        if !header {
+            return Err(ParseError {
+                line: 1,
+                column: 1,
+                kind: ParseErrorKind::MissingHeader,
+            });
+        }
+
+        Ok(set)
+    }
+
+    /// Parses the settings of a track line.
+    fn parse_track_settings(&mut self, t: usize, tokens: &mut Tokens) -> Result<(), ParseError> {
+        while let Some((key, value)) = tokens.next_setting()? {
+            let settings = &mut self.tracks[t];
+            match key.text {
+                "length" => self.patterns[t].set_length(value.number(1, NUM_STEPS)? as u32),
+                "enable" => settings.enable = value.switch()?,
+                "delay" => settings.delay = value.parse()?,
+                "gate" => settings.gate = value.parse()?,
+                "choke" => {
+                    let group: u8 = value.parse()?;
+                    settings.choke_group = (group > 0).then_some(group);
+                }
+                "accent" => settings.accent_lane = Some(value.number(0, 127)? as u8),
+                _ => return Err(key.error(ParseErrorKind::UnknownKey)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses the characters of a steps line.
+    fn parse_steps(&mut self, t: usize, steps: &Token) -> Result<(), ParseError> {
+        if steps.text.chars().count() > NUM_STEPS {
+            return Err(steps.error(ParseErrorKind::TooManySteps));
+        }
+
+        for (s, c) in steps.text.chars().enumerate() {
+            let step = &mut self.patterns[t].steps_mut()[s];
+            let vel = match c {
+                '_' => {
+                    step.disable();
+                    step.set_event(None);
+                    continue;
+                }
+                'o' => {
+                    step.enable();
+                    step.set_event(None);
+                    continue;
+                }
+                'V' => Velocity::Custom(100),
+                c => match velocity_from_char(c) {
+                    Some(vel) => vel,
+                    None => {
+                        return Err(ParseError {
+                            line: steps.line,
+                            column: steps.column + s,
+                            kind: ParseErrorKind::InvalidStep,
+                        });
+                    }
+                },
+            };
+
+            step.enable();
+            step.set_event(Some(StepEvent::NoteOn {
+                pitch: Pitch::Default,
+                vel,
+            }));
+        }
+
+        Ok(())
+    }
+}
+
+impl<const NUM_TRACKS: usize, const NUM_STEPS: usize> fmt::Display
+    for PatternSet<NUM_TRACKS, NUM_STEPS>
+{
+    /// Writes the notation, omitting tracks and settings with default values.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{HEADER} {NOTATION_VERSION}")?;
+
+        let default_pattern = Pattern::<NUM_STEPS>::new();
+        let default_settings = TrackSettings::default();
+
+        for (t, (pattern, settings)) in self.patterns.iter().zip(&self.tracks).enumerate() {
+            let steps = pattern.steps();
+            let empty = steps.iter().all(is_default_step);
+            if empty
+                && pattern.length() == default_pattern.length()
+                && *settings == default_settings
+            {
+                continue;
+            }
+
+            write!(f, "track {}", t + 1)?;
+            if pattern.length() != default_pattern.length() {
+                write!(f, " length={}", pattern.length())?;
+            }
+            if settings.enable != default_settings.enable {
+                write!(f, " enable={}", if settings.enable { "on" } else { "off" })?;
+            }
+            if settings.delay != default_settings.delay {
+                write!(f, " delay={}", settings.delay)?;
+            }
+            if settings.gate != default_settings.gate {
+                write!(f, " gate={}", settings.gate)?;
+            }
+            if let Some(group) = settings.choke_group {
+                write!(f, " choke={group}")?;
+            }
+            if let Some(accent) = settings.accent_lane {
+                write!(f, " accent={accent}")?;
+            }
+            writeln!(f)?;
+
+            write!(f, "steps ")?;
+            for step in steps {
+                let c = match (step.enabled(), first_note(step)) {
+                    (false, _) => '_',
+                    (true, None) => 'o',
+                    (true, Some((_, Velocity::Custom(_)))) => 'V',
+                    (true, Some((_, vel))) => velocity_to_char(vel).unwrap_or('X'),
+                };
+                write!(f, "{c}")?;
+            }
+            writeln!(f)?;
+
+            for (s, step) in steps.iter().enumerate() {
+                if !is_plain_step(step) {
+                    write!(f, "step {}", s + 1)?;
+                    write_step_settings(f, step)?;
+                    writeln!(f)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the settings of a step that differ from the defaults.
+fn write_step_settings(f: &mut fmt::Formatter<'_>, step: &Step) -> fmt::Result {
+    let default_step = Step::default();
+
+    if step.enabled()
+        && let Some((pitch, vel)) = first_note(step)
+    {
+        if let Velocity::Custom(value) = vel {
+            write!(f, " vel={value}")?;
+        }
+        if let Pitch::Custom(value) = pitch {
+            write!(f, " pitch={value}")?;
+        }
+
+        let mut layers = notes(step).skip(1).peekable();
+        if layers.peek().is_some() {
+            write!(f, " layers=")?;
+            for (n, (layer_pitch, layer_vel)) in layers.enumerate() {
+                if n > 0 {
+                    write!(f, ",")?;
+                }
+                match layer_pitch {
+                    Pitch::Custom(value) => write!(f, "{value}")?,
+                    _ => write!(f, "*")?,
+                }
+                if layer_vel != vel {
+                    match layer_vel {
+                        Velocity::Custom(value) => write!(f, "@{value}")?,
+                        layer_vel => write!(f, "@{}", velocity_to_char(layer_vel).unwrap_or('X'))?,
+                    }
+                }
+            }
+        }
+    }
+
+    if step.probability() != default_step.probability() {
+        write!(f, " prob={}", step.probability())?;
+    }
+    if step.condition() != default_step.condition() {
+        write!(f, " cond={}", step.condition())?;
+    }
+    match step.gate() {
+        Some(Gate::Length(percent)) => write!(f, " gate={percent}")?,
+        Some(Gate::Tie) => write!(f, " gate=tie")?,
+        None => {}
+    }
+    if step.nudge() != default_step.nudge() {
+        write!(f, " nudge={}", step.nudge())?;
+    }
+    if step.ratchets() != default_step.ratchets() {
+        write!(f, " ratchets={}", step.ratchets())?;
+    }
+    if step.ramp() != default_step.ramp() {
+        let ramp = match step.ramp() {
+            Ramp::Up => "up",
+            Ramp::Down => "down",
+            _ => "flat",
+        };
+        write!(f, " ramp={ramp}")?;
+    }
+    if step.accent() != default_step.accent() {
+        write!(f, " accent={}", if step.accent() { "on" } else { "off" })?;
+    }
+
+    Ok(())
+}
+
+/// Parses the settings of a step line.
+fn parse_step_settings(step: &mut Step, tokens: &mut Tokens) -> Result<(), ParseError> {
+    while let Some((key, value)) = tokens.next_setting()? {
+        match key.text {
+            "vel" => {
+                let vel = Velocity::Custom(value.number(0, 127)? as u8);
+                set_first_note(step, &value, |pitch, _| (pitch, vel))?;
+            }
+            "pitch" => {
+                let pitch = Pitch::Custom(value.parse()?);
+                set_first_note(step, &value, |_, vel| (pitch, vel))?;
+            }
+            "layers" => {
+                let Some((_, vel)) = first_note(step) else {
+                    return Err(value.error(ParseErrorKind::MissingNote));
+                };
+                step.events_mut().truncate(1);
+                for layer in value.split(',') {
+                    let (pitch, layer_vel) = match layer.text.split_once('@') {
+                        Some((pitch, layer_vel)) => (pitch, Some(layer_vel)),
+                        None => (layer.text, None),
+                    };
+                    let pitch = match pitch {
+                        "*" => Pitch::Default,
+                        pitch => Pitch::Custom(
+                            pitch
+                                .parse()
+                                .map_err(|_| layer.error(ParseErrorKind::InvalidValue))?,
+                        ),
+                    };
+                    let vel = match layer_vel {
+                        None => vel,
+                        Some(layer_vel) => parse_velocity(layer_vel)
+                            .ok_or(layer.error(ParseErrorKind::InvalidValue))?,
+                    };
+                    step.add_event(StepEvent::NoteOn { pitch, vel })
+                        .map_err(|_| layer.error(ParseErrorKind::TooManyNotes))?;
+                }
+            }
+            "prob" => step.set_probability(value.number(0, 100)? as u8),
+            "cond" => step.set_condition(
+                parse_condition(value.text).ok_or(value.error(ParseErrorKind::InvalidValue))?,
+            ),
+            "gate" => step.set_gate(Some(match value.text {
+                "tie" => Gate::Tie,
+                _ => Gate::Length(value.parse()?),
+            })),
+            "nudge" => step.set_nudge(value.parse()?),
+            "ratchets" => step.set_ratchets(value.number(1, MAX_RATCHETS as usize)? as u8),
+            "ramp" => step.set_ramp(match value.text {
+                "flat" => Ramp::Flat,
+                "up" => Ramp::Up,
+                "down" => Ramp::Down,
+                _ => return Err(value.error(ParseErrorKind::InvalidValue)),
+            }),
+            "accent" => step.set_accent(value.switch()?),
+            _ => return Err(key.error(ParseErrorKind::UnknownKey)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces the first note of an enabled step.
+/// - `f`: Function returning the new pitch and velocity from the current ones.
+fn set_first_note(
+    step: &mut Step,
+    token: &Token,
+    f: impl FnOnce(Pitch, Velocity) -> (Pitch, Velocity),
+) -> Result<(), ParseError> {
+    if !step.enabled() {
+        return Err(token.error(ParseErrorKind::MissingNote));
+    }
+
+    match step.events_mut().first_mut() {
+        Some(StepEvent::NoteOn { pitch, vel }) => {
+            (*pitch, *vel) = f(*pitch, *vel);
+            Ok(())
+        }
+        _ => Err(token.error(ParseErrorKind::MissingNote)),
+    }
+}
+
+/// Returns the pitch and velocity of the notes of a step.
+fn notes(step: &Step) -> impl Iterator<Item = (Pitch, Velocity)> + '_ {
+    step.events().iter().filter_map(|event| match event {
+        StepEvent::NoteOn { pitch, vel } => Some((*pitch, *vel)),
+        _ => None,
+    })
+}
+
+/// Returns the pitch and velocity of the first note of a step.
+fn first_note(step: &Step) -> Option<(Pitch, Velocity)> {
+    notes(step).next()
+}
+
+/// Returns if a step has no settings besides its state.
+fn is_plain_step(step: &Step) -> bool {
+    let default_step = Step::default();
+    let plain_note = match first_note(step) {
+        Some((Pitch::Default, Velocity::Custom(_))) => false,
+        Some((Pitch::Default, _)) => notes(step).count() == 1,
+        Some(_) => false,
+        None => true,
+    };
+
+    (plain_note || !step.enabled())
+        && step.probability() == default_step.probability()
+        && step.condition() == default_step.condition()
+        && step.gate() == default_step.gate()
+        && step.nudge() == default_step.nudge()
+        && step.ratchets() == default_step.ratchets()
+        && step.ramp() == default_step.ramp()
+        && step.accent() == default_step.accent()
+}
+
+/// Returns if a step is disabled and has no settings.
+fn is_default_step(step: &Step) -> bool {
+    !step.enabled() && is_plain_step(step)
+}
+
+/// Returns the velocity of a step character.
+fn velocity_from_char(c: char) -> Option<Velocity> {
+    match c {
+        'X' => Some(Velocity::Default),
+        'A' => Some(Velocity::Accent),
+        'x' => Some(Velocity::Weak),
+        '.' => Some(Velocity::Ghost),
+        _ => None,
+    }
+}
+
+/// Returns the step character of a velocity, `None` for individual velocities.
+fn velocity_to_char(vel: Velocity) -> Option<char> {
+    match vel {
+        Velocity::Default => Some('X'),
+        Velocity::Accent => Some('A'),
+        Velocity::Weak => Some('x'),
+        Velocity::Ghost => Some('.'),
+        _ => None,
+    }
+}
+
+/// Returns the velocity from a step character or a value.
+fn parse_velocity(text: &str) -> Option<Velocity> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if !c.is_ascii_digit() => velocity_from_char(c),
+        _ => match text.parse::<u8>() {
+            Ok(value) if value <= 127 => Some(Velocity::Custom(value)),
+            _ => None,
+        },
+    }
+}
+
+/// Returns the condition from its text representation.
+fn parse_condition(text: &str) -> Option<Condition> {
+    match text {
+        "always" => Some(Condition::Always),
+        "1st" => Some(Condition::First),
+        "!1st" => Some(Condition::NotFirst),
+        "fill" => Some(Condition::Fill),
+        "!fill" => Some(Condition::NotFill),
+        ratio => {
+            let (pos, len) = ratio.split_once(':')?;
+            Some(Condition::Ratio {
+                pos: pos.parse().ok()?,
+                len: len.parse().ok()?,
+            })
+        }
+    }
+}
+
+/// Error parsing the notation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseError {
+    /// Line number, starting at 1.
+    pub line: usize,
+
+    /// Column of the character in the line, starting at 1.
+    pub column: usize,
+
+    /// Cause of the error.
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.kind
+        )
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+/// Cause of a parse error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseErrorKind {
+    /// The first line is not the header with the version.
+    MissingHeader,
+
+    /// The version is newer than supported.
+    UnsupportedVersion,
+
+    /// The line starts with an unknown keyword.
+    UnknownKeyword,
+
+    /// A step line appears before the first track line.
+    MissingTrack,
+
+    /// A value is missing at the end of the line.
+    MissingValue,
+
+    /// A setting has an unknown key.
+    UnknownKey,
+
+    /// A value can't be parsed or is out of range.
+    InvalidValue,
+
+    /// A step character is unknown.
+    InvalidStep,
+
+    /// The steps line is longer than the pattern.
+    TooManySteps,
+
+    /// A note setting is used on a step without a note.
+    MissingNote,
+
+    /// A step has more notes than supported.
+    TooManyNotes,
+
+    /// The line has unexpected content at the end.
+    UnexpectedToken,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Self::MissingHeader => "expected header with version",
+            Self::UnsupportedVersion => "unsupported version",
+            Self::UnknownKeyword => "unknown keyword",
+            Self::MissingTrack => "expected track line first",
+            Self::MissingValue => "missing value",
+            Self::UnknownKey => "unknown setting",
+            Self::InvalidValue => "invalid value",
+            Self::InvalidStep => "invalid step character",
+            Self::TooManySteps => "too many steps",
+            Self::MissingNote => "step has no note",
+            Self::TooManyNotes => "too many notes on step",
+            Self::UnexpectedToken => "unexpected content",
+        };
+        write!(f, "{message}")
+    }
+}
+
+/// Part of a line separated by whitespace, or a part of such a part.
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    /// Text of the token.
+    text: &'a str,
+
+    /// Line number, starting at 1.
+    line: usize,
+
+    /// Column of the first character, starting at 1.
+    column: usize,
+}
+
+impl<'a> Token<'a> {
+    /// Returns an error at the position of the token.
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            line: self.line,
+            column: self.column,
+            kind,
+        }
+    }
+
+    /// Returns the part of the token starting at byte `offset`.
+    fn sub(&self, offset: usize, text: &'a str) -> Self {
+        Self {
+            text,
+            line: self.line,
+            column: self.column + self.text[..offset].chars().count(),
+        }
+    }
+
+    /// Returns the value parsed from the token.
+    fn parse<T: core::str::FromStr>(&self) -> Result<T, ParseError> {
+        self.text
+            .parse()
+            .map_err(|_| self.error(ParseErrorKind::InvalidValue))
+    }
+
+    /// Returns the number parsed from the token, if it is within `min..=max`.
+    fn number(&self, min: usize, max: usize) -> Result<usize, ParseError> {
+        match self.parse::<usize>()? {
+            value if (min..=max).contains(&value) => Ok(value),
+            _ => Err(self.error(ParseErrorKind::InvalidValue)),
+        }
+    }
+
+    /// Returns the state of an `on` or `off` value.
+    fn switch(&self) -> Result<bool, ParseError> {
+        match self.text {
+            "on" => Ok(true),
+            "off" => Ok(false),
+            _ => Err(self.error(ParseErrorKind::InvalidValue)),
+        }
+    }
+
+    /// Returns the parts of the token separated by `separator`.
+    fn split(&self, separator: char) -> impl Iterator<Item = Token<'a>> + '_ {
+        let mut offset = 0;
+        self.text.split(separator).map(move |part| {
+            let token = self.sub(offset, part);
+            offset += part.len() + separator.len_utf8();
+            token
+        })
+    }
+}
+
+/// Tokens of a line.
+struct Tokens<'a> {
+    /// Line content.
+    line: &'a str,
+
+    /// Line number, starting at 1.
+    line_no: usize,
+
+    /// Byte position of the remaining content.
+    offset: usize,
+}
+
+impl<'a> Tokens<'a> {
+    /// Returns a new instance for a line.
+    fn new(line: &'a str, line_no: usize) -> Self {
+        Self {
+            line,
+            line_no,
+            offset: 0,
+        }
+    }
+
+    /// Returns the next token.
+    fn next(&mut self) -> Option<Token<'a>> {
+        let rest = &self.line[self.offset..];
+        let start = self.offset + (rest.len() - rest.trim_start().len());
+        let rest = &self.line[start..];
+        let length = rest.find(char::is_whitespace).unwrap_or(rest.len());
+
+        if length == 0 {
+            return None;
+        }
+
+        self.offset = start + length;
+
+        Some(Token {
+            text: &self.line[start..self.offset],
+            line: self.line_no,
+            column: self.line[..start].chars().count() + 1,
+        })
+    }
+
+    /// Returns the next token or an error if the line has ended.
+    fn expect(&mut self) -> Result<Token<'a>, ParseError> {
+        self.next().ok_or(ParseError {
+            line: self.line_no,
+            column: self.line.chars().count() + 1,
+            kind: ParseErrorKind::MissingValue,
+        })
+    }
+
+    /// Returns an error if the line has more tokens.
+    fn finish(&mut self) -> Result<(), ParseError> {
+        match self.next() {
+            Some(token) => Err(token.error(ParseErrorKind::UnexpectedToken)),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the key and value of the next `key=value` setting.
+    fn next_setting(&mut self) -> Result<Option<(Token<'a>, Token<'a>)>, ParseError> {
+        let Some(token) = self.next() else {
+            return Ok(None);
+        };
+
+        match token.text.split_once('=') {
+            Some((key, value)) if !value.is_empty() => {
+                Ok(Some((token.sub(0, key), token.sub(key.len() + 1, value))))
+            }
+            Some((key, _)) => Err(token
+                .sub(key.len() + 1, "")
+                .error(ParseErrorKind::MissingValue)),
+            None => Err(token.error(ParseErrorKind::UnexpectedToken)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Notation with all settings, written the way the formatter writes it.
+    const TEXT: &str = "\
+dr-seq 2
+track 1 length=12 delay=-4 gate=75
+steps XAx.V_o_X_______
+step 1 pitch=38 layers=42,*@A
+step 2 prob=50 cond=1:2
+step 3 gate=tie nudge=-12
+step 5 vel=96 ratchets=3 ramp=up
+step 9 cond=fill gate=25 ratchets=2 ramp=down
+track 2 enable=off
+steps X_______________
+";
+
+    /// Returns the position and kind of the error parsing `text`.
+    fn error(text: &str) -> ParseError {
+        PatternSet::<2, 16>::parse(text).unwrap_err()
+    }
+
+    #[test]
+    fn round_trip() {
+        let set = PatternSet::<2, 16>::parse(TEXT).unwrap();
+        assert_eq!(set.to_string(), TEXT);
+        assert_eq!(PatternSet::parse(&set.to_string()), Ok(set.clone()));
+
+        let pattern = &set.patterns[0];
+        assert_eq!(pattern.length(), 12);
+        assert_eq!(set.tracks[0].delay, -4);
+        assert_eq!(set.tracks[0].gate, 75);
+        assert!(!set.tracks[1].enable);
+
+        let notes = |s| notes(pattern.step(s)).collect::<Vec<_>>();
+        assert_eq!(
+            notes(0),
+            [
+                (Pitch::Custom(38), Velocity::Default),
+                (Pitch::Custom(42), Velocity::Default),
+                (Pitch::Default, Velocity::Accent),
+            ]
+        );
+        assert_eq!(notes(1), [(Pitch::Default, Velocity::Accent)]);
+        assert_eq!(notes(2), [(Pitch::Default, Velocity::Weak)]);
+        assert_eq!(notes(3), [(Pitch::Default, Velocity::Ghost)]);
+        assert_eq!(notes(4), [(Pitch::Default, Velocity::Custom(96))]);
+        assert!(!pattern.step(5).enabled());
+        assert!(pattern.step(6).enabled() && pattern.step(6).events().is_empty());
+
+        assert_eq!(pattern.step(1).probability(), 50);
+        assert_eq!(pattern.step(2).gate(), Some(Gate::Tie));
+        assert_eq!(pattern.step(2).nudge(), -12);
+        assert_eq!(pattern.step(4).ratchets(), 3);
+        assert_eq!(pattern.step(4).ramp(), Ramp::Up);
+        assert_eq!(pattern.step(8).condition(), Condition::Fill);
+        assert_eq!(pattern.step(8).gate(), Some(Gate::Length(25)));
+    }
+
+    #[test]
+    fn errors_have_positions() {
+        let at = |line, column, kind| ParseError { line, column, kind };
+
+        assert_eq!(error("foo 1"), at(1, 1, ParseErrorKind::MissingHeader));
+        assert_eq!(
+            error("dr-seq 3"),
+            at(1, 8, ParseErrorKind::UnsupportedVersion)
+        );
+        assert_eq!(
+            error("dr-seq 2\ntrack 1 size=4"),
+            at(2, 9, ParseErrorKind::UnknownKey)
+        );
+        assert_eq!(
+            error("dr-seq 2\ntrack 1 length="),
+            at(2, 16, ParseErrorKind::MissingValue)
+        );
+        assert_eq!(
+            error("dr-seq 2\n\ntrack 1\nsteps X________________"),
+            at(4, 7, ParseErrorKind::TooManySteps)
+        );
+    }
+
+    #[test]
+    fn choke_and_accent_round_trip() {
+        let mut set = PatternSet::<2, 16>::new();
+        set.tracks[0].choke_group = Some(1);
+        set.tracks[1].choke_group = Some(1);
+        set.tracks[1].accent_lane = Some(10);
+        let step = &mut set.patterns[1].steps_mut()[4];
+        step.enable();
+        step.set_event(Some(StepEvent::NoteOn {
+            pitch: Pitch::Default,
+            vel: Velocity::Default,
+        }));
+        step.set_accent(true);
+
+        let text = set.to_string();
+        assert!(text.starts_with("dr-seq 2\n"));
+        assert!(text.contains(" choke=1"));
+        assert!(text.contains(" accent=10"));
+        assert!(text.contains(" accent=on"));
+        assert_eq!(PatternSet::parse(&text), Ok(set));
+    }
+
+    #[test]
+    fn choke_group_zero_is_none() {
+        let set = PatternSet::<2, 16>::parse("dr-seq 2\ntrack 1 choke=0\n").unwrap();
+        assert_eq!(set.tracks[0].choke_group, None);
+    }
+}
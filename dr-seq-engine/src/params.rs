@@ -34,3 +34,133 @@ pub enum Velocity {
     /// Individual setting.
     Custom(u8),
 }
+
+/// Trigger condition variants.
+///
+/// Loop numbers are counted from 1 for the first loop of the pattern after playback started.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum Condition {
+    /// Trigger on every loop.
+    #[default]
+    Always,
+
+    /// Trigger on loop `pos` of a cycle of `len` loops, e.g. 3:4.
+    Ratio {
+        /// Position within the cycle, starting at 1.
+        pos: u8,
+
+        /// Length of the cycle in loops.
+        len: u8,
+    },
+
+    /// Trigger on the first loop only.
+    First,
+
+    /// Trigger on all loops except the first one.
+    NotFirst,
+
+    /// Trigger only when fill mode is active.
+    Fill,
+
+    /// Trigger only when fill mode is inactive.
+    NotFill,
+}
+
+impl Condition {
+    /// Returns if the condition is met.
+    /// - `loop_no`: Number of the current loop, starting at 0 for the first loop.
+    /// - `fill`: Fill mode state.
+    pub fn is_met(&self, loop_no: u32, fill: bool) -> bool {
+        match *self {
+            Self::Always => true,
+            Self::Ratio { pos, len } => {
+                len == 0 || loop_no % len as u32 == (pos.max(1) as u32 - 1) % len as u32
+            }
+            Self::First => loop_no == 0,
+            Self::NotFirst => loop_no != 0,
+            Self::Fill => fill,
+            Self::NotFill => !fill,
+        }
+    }
+}
+
+impl core::fmt::Display for Condition {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Always => write!(f, "always"),
+            Self::Ratio { pos, len } => write!(f, "{pos}:{len}"),
+            Self::First => write!(f, "1st"),
+            Self::NotFirst => write!(f, "!1st"),
+            Self::Fill => write!(f, "fill"),
+            Self::NotFill => write!(f, "!fill"),
+        }
+    }
+}
+
+/// Note length variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum Gate {
+    /// Length in percent of a step.
+    Length(u16),
+
+    /// Hold the note until the next note is triggered on the same track.
+    Tie,
+}
+
+impl Default for Gate {
+    /// Returns a length of a 1/32 note.
+    fn default() -> Self {
+        Self::Length(50)
+    }
+}
+
+impl Gate {
+    /// Returns the length in pulses or `None` for a tied note.
+    /// - `pp16th`: Pulses per 16th note, which is the length of a step.
+    ///
+    /// The minimum length of a note is 1 pulse.
+    pub fn pulses(&self, pp16th: u32) -> Option<u32> {
+        match *self {
+            Self::Length(percent) => Some((percent as u32 * pp16th / 100).max(1)),
+            Self::Tie => None,
+        }
+    }
+}
+
+/// Velocity ramp variants for ratchets.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum Ramp {
+    /// All hits with the same velocity.
+    #[default]
+    Flat,
+
+    /// Increasing velocity.
+    Up,
+
+    /// Decreasing velocity.
+    Down,
+}
+
+impl Ramp {
+    /// Minimum velocity scale in percent at the quiet end of a ramp.
+    const MIN_SCALE: u32 = 25;
+
+    /// Returns the velocity scale in percent for hit number `hit` of `hits`.
+    pub fn scale(&self, hit: u32, hits: u32) -> u8 {
+        if hits < 2 {
+            return 100;
+        }
+
+        let hit = hit.min(hits - 1);
+        let position = match self {
+            Self::Flat => return 100,
+            Self::Up => hit,
+            Self::Down => hits - 1 - hit,
+        };
+
+        (Self::MIN_SCALE + (100 - Self::MIN_SCALE) * position / (hits - 1)) as u8
+    }
+}
@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 use crate::step::Step;
 
 /// Sequencer pattern with capacity of `CAPACITY`.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Pattern<const CAPACITY: usize> {
     /// Array of steps.
     #[serde(with = "serde_arrays")]
@@ -19,7 +19,7 @@ impl<const NUM_STEPS: usize> Default for Pattern<NUM_STEPS> {
     fn default() -> Self {
         Self {
             steps: core::array::from_fn(|_| Step::default()),
-            length: 16,
+            length: NUM_STEPS as u32,
         }
     }
 }
@@ -36,8 +36,10 @@ impl<const NUM_STEPS: usize> Pattern<NUM_STEPS> {
     }
 
     /// Sets the active length in steps.
+    ///
+    /// The length is limited to the range from 1 to the capacity.
     pub fn set_length(&mut self, steps: u32) {
-        self.length = steps;
+        self.length = steps.clamp(1, NUM_STEPS as u32);
     }
 
     /// Returns the capacity in steps.
@@ -55,6 +57,11 @@ impl<const NUM_STEPS: usize> Pattern<NUM_STEPS> {
         &mut self.steps
     }
 
+    /// Returns a reference to the steps within the active length.
+    pub fn active_steps(&self) -> &[Step] {
+        &self.steps[..(self.length as usize).min(NUM_STEPS)]
+    }
+
     /// Returns a reference to a specific step.
     pub fn step(&self, step_no: u32) -> &Step {
         &self.steps[step_no as usize]
@@ -0,0 +1,375 @@
+//! Offline rendering of patterns.
+
+use crate::chain::Chain;
+use crate::pattern::Pattern;
+use crate::sequencer::{Sequencer, SequencerEvent, SequencerParams};
+
+/// Settings for rendering.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderSettings {
+    /// Tempo in beats per minute.
+    pub tempo: f64,
+
+    /// Number of bars to render, assuming a 4/4 time signature.
+    pub bars: u32,
+
+    /// Pulses per quarter note of the clock.
+    pub ppq: u32,
+
+    /// Sample rate in Hz used for the sample positions.
+    pub sample_rate: f64,
+
+    /// Seed for the random number generators of the tracks.
+    pub seed: u32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            tempo: 120.0,
+            bars: 1,
+            ppq: 384,
+            sample_rate: 48000.0,
+            seed: 0,
+        }
+    }
+}
+
+impl RenderSettings {
+    /// Returns the duration of a pulse in samples.
+    pub fn pulse_duration(&self) -> f64 {
+        60.0 / (self.tempo * self.ppq as f64) * self.sample_rate
+    }
+
+    /// Returns the total number of pulses.
+    pub fn pulses(&self) -> u32 {
+        self.bars.saturating_mul(self.ppq * 4)
+    }
+}
+
+/// Event with its position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderedEvent {
+    /// Position in pulses from the start.
+    pub pulse_no: u32,
+
+    /// Position in samples from the start.
+    pub sample_no: u64,
+
+    /// The event itself.
+    pub event: SequencerEvent,
+}
+
+/// Iterator over the events of a rendering.
+///
+/// The sequencer is updated pulse by pulse, the same way as with a clock running from the
+/// start of the song. Notes still sounding at the end are stopped at the end position.
+#[derive(Debug)]
+pub struct Render<'a, const NUM_TRACKS: usize, const NUM_STEPS: usize> {
+    /// Sequencer generating the events.
+    sequencer: Sequencer<NUM_TRACKS, NUM_STEPS>,
+
+    /// Parameters for the playback.
+    params: SequencerParams<NUM_TRACKS>,
+
+    /// Settings for the rendering.
+    settings: RenderSettings,
+
+    /// Chain and the sets of patterns it refers to by number.
+    chain: Option<(&'a Chain, &'a [[Pattern<NUM_STEPS>; NUM_TRACKS]])>,
+
+    /// Number of the pattern set loaded from the chain.
+    chain_pattern: Option<u32>,
+
+    /// Fill mode of the playback parameters, combined with the fill mode of a chain entry.
+    fill: bool,
+
+    /// Number of the pulse the pending events belong to.
+    pulse_no: u32,
+
+    /// Number of the next pulse to update the sequencer with.
+    next_pulse_no: u32,
+
+    /// Flag if the sequencer was stopped at the end.
+    stopped: bool,
+}
+
+impl<'a, const NUM_TRACKS: usize, const NUM_STEPS: usize> Render<'a, NUM_TRACKS, NUM_STEPS> {
+    /// Returns a new instance rendering with a prepared sequencer.
+    /// - `sequencer`: Sequencer with the patterns to render.
+    /// - `params`: Parameters for the playback.
+    /// - `settings`: Settings for the rendering.
+    pub fn new(
+        mut sequencer: Sequencer<NUM_TRACKS, NUM_STEPS>,
+        params: &SequencerParams<NUM_TRACKS>,
+        settings: &RenderSettings,
+    ) -> Self {
+        sequencer.start(settings.seed);
+
+        Self {
+            sequencer,
+            params: params.clone(),
+            settings: *settings,
+            chain: None,
+            chain_pattern: None,
+            fill: params.fill,
+            pulse_no: 0,
+            next_pulse_no: 0,
+            stopped: false,
+        }
+    }
+
+    /// Plays the patterns following a chain instead of the patterns of the sequencer.
+    /// - `chain`: Chain to follow.
+    /// - `patterns`: Sets of patterns, indexed by the pattern numbers of the chain entries.
+    ///
+    /// Entries referring to missing pattern sets keep the previous patterns.
+    pub fn with_chain(
+        mut self,
+        chain: &'a Chain,
+        patterns: &'a [[Pattern<NUM_STEPS>; NUM_TRACKS]],
+    ) -> Self {
+        self.chain = Some((chain, patterns));
+        self
+    }
+
+    /// Switches the patterns at the start of a bar according to the chain.
+    fn follow_chain(&mut self, pulse_no: u32) {
+        let Some((chain, patterns)) = self.chain else {
+            return;
+        };
+
+        let bar_pulses = self.settings.ppq * 4;
+        if !pulse_no.is_multiple_of(bar_pulses.max(1)) {
+            return;
+        }
+
+        match chain.position(pulse_no / bar_pulses) {
+            Some(position) => {
+                let pattern_no = position.entry.pattern;
+                if self.chain_pattern != Some(pattern_no)
+                    && let Some(patterns) = patterns.get(pattern_no as usize)
+                {
+                    self.sequencer.patterns_mut().clone_from(patterns);
+                    self.chain_pattern = Some(pattern_no);
+                }
+                self.params.fill = self.fill || position.entry.fill;
+            }
+            // Without a position, e.g. for an empty chain, the plugin plays without the
+            // fill mode of the chain.
+            None => self.params.fill = self.fill,
+        }
+    }
+
+    /// Returns the position in samples of a pulse.
+    fn sample_no(&self, pulse_no: u32) -> u64 {
+        // Rounding as done by the clock of the plugin.
+        (pulse_no as f64 * self.settings.pulse_duration() + 0.5) as u64
+    }
+}
+
+impl<const NUM_TRACKS: usize, const NUM_STEPS: usize> Iterator
+    for Render<'_, NUM_TRACKS, NUM_STEPS>
+{
+    type Item = RenderedEvent;
+
+    /// Returns the next event.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.sequencer.next_event() {
+                return Some(RenderedEvent {
+                    pulse_no: self.pulse_no,
+                    sample_no: self.sample_no(self.pulse_no),
+                    event,
+                });
+            }
+
+            if self.next_pulse_no < self.settings.pulses() {
+                self.pulse_no = self.next_pulse_no;
+                self.follow_chain(self.pulse_no);
+                self.sequencer
+                    .update(self.pulse_no, self.settings.ppq, &self.params);
+                self.next_pulse_no += 1;
+            } else if !self.stopped {
+                self.pulse_no = self.next_pulse_no;
+                self.sequencer.stop();
+                self.stopped = true;
+            } else {
+                return None;
+            }
+        }
+    }
+}
+
+/// Renders patterns and returns an iterator over the events.
+/// - `patterns`: Patterns for the tracks.
+/// - `repeat_priority`: Order of the tracks for step repeats, see
+///   [`Sequencer::set_repeat_priority`].
+/// - `params`: Parameters for the playback.
+/// - `settings`: Settings for the rendering.
+///
+/// The events are ordered by their position.
+pub fn render<const NUM_TRACKS: usize, const NUM_STEPS: usize>(
+    patterns: &[Pattern<NUM_STEPS>; NUM_TRACKS],
+    repeat_priority: &[usize],
+    params: &SequencerParams<NUM_TRACKS>,
+    settings: &RenderSettings,
+) -> Render<'static, NUM_TRACKS, NUM_STEPS> {
+    let mut sequencer = Sequencer::new();
+    sequencer.set_repeat_priority(repeat_priority);
+    sequencer.patterns_mut().clone_from(patterns);
+
+    Render::new(sequencer, params, settings)
+}
+
+/// Renders a chain of patterns and returns an iterator over the events.
+/// - `chain`: Chain to follow.
+/// - `patterns`: Sets of patterns, indexed by the pattern numbers of the chain entries.
+/// - `repeat_priority`: Order of the tracks for step repeats, see
+///   [`Sequencer::set_repeat_priority`].
+/// - `params`: Parameters for the playback.
+/// - `settings`: Settings for the rendering.
+///
+/// Use the number of bars of the chain in `settings` to render it once.
+pub fn render_chain<'a, const NUM_TRACKS: usize, const NUM_STEPS: usize>(
+    chain: &'a Chain,
+    patterns: &'a [[Pattern<NUM_STEPS>; NUM_TRACKS]],
+    repeat_priority: &[usize],
+    params: &SequencerParams<NUM_TRACKS>,
+    settings: &RenderSettings,
+) -> Render<'a, NUM_TRACKS, NUM_STEPS> {
+    let mut sequencer = Sequencer::new();
+    sequencer.set_repeat_priority(repeat_priority);
+
+    Render::new(sequencer, params, settings).with_chain(chain, patterns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::ChainEntry;
+    use crate::params::{Condition, Pitch, Velocity};
+    use crate::step::StepEvent;
+
+    /// Returns a pattern with notes on the first step only.
+    fn first_step_pattern() -> Pattern<16> {
+        let mut pattern = Pattern::new();
+        let step = &mut pattern.steps_mut()[0];
+        step.enable();
+        step.set_event(Some(StepEvent::NoteOn {
+            pitch: Pitch::Default,
+            vel: Velocity::Default,
+        }));
+        pattern
+    }
+
+    /// Returns the events of a sequencer driven pulse by pulse like the plugin does.
+    fn play(
+        mut sequencer: Sequencer<2, 16>,
+        params: &SequencerParams<2>,
+        settings: &RenderSettings,
+    ) -> Vec<(u32, SequencerEvent)> {
+        let mut events = Vec::new();
+        sequencer.start(settings.seed);
+        for pulse_no in 0..settings.pulses() {
+            sequencer.update(pulse_no, settings.ppq, params);
+            while let Some(event) = sequencer.next_event() {
+                events.push((pulse_no, event));
+            }
+        }
+        sequencer.stop();
+        while let Some(event) = sequencer.next_event() {
+            events.push((settings.pulses(), event));
+        }
+        events
+    }
+
+    #[test]
+    fn render_uses_repeat_priority() {
+        let patterns = [first_step_pattern(), first_step_pattern()];
+        let mut params = SequencerParams::<2>::default();
+        params.manglers.flame = true;
+        let settings = RenderSettings::default();
+        let priority = [1, 0];
+
+        let mut sequencer = Sequencer::new();
+        sequencer.set_repeat_priority(&priority);
+        sequencer.patterns_mut().clone_from(&patterns);
+        let played = play(sequencer, &params, &settings);
+
+        let rendered: Vec<_> = render(&patterns, &priority, &params, &settings)
+            .map(|event| (event.pulse_no, event.event))
+            .collect();
+        assert_eq!(rendered, played);
+
+        // The repeated notes are played by the track with the highest priority.
+        let repeating_track = |events: &[(u32, SequencerEvent)]| {
+            events.iter().find_map(|(pulse_no, event)| match event {
+                SequencerEvent::NoteOn { track, .. } if *pulse_no > 0 => Some(*track),
+                _ => None,
+            })
+        };
+        assert_eq!(repeating_track(&rendered), Some(1));
+
+        let natural: Vec<_> = render(&patterns, &[], &params, &settings)
+            .map(|event| (event.pulse_no, event.event))
+            .collect();
+        assert_eq!(repeating_track(&natural), Some(0));
+    }
+
+    #[test]
+    fn render_chain_fill_matches_playback() {
+        let mut pattern = first_step_pattern();
+        pattern.steps_mut()[0].set_condition(Condition::Fill);
+        let patterns = [[pattern.clone(), pattern]];
+
+        let mut chain = Chain::new();
+        chain
+            .push(ChainEntry {
+                fill: true,
+                ..Default::default()
+            })
+            .unwrap();
+
+        let settings = RenderSettings {
+            bars: 2,
+            ..Default::default()
+        };
+        let bar_pulses = settings.ppq * 4;
+
+        let note_on_bars = |chain: &Chain| -> Vec<_> {
+            render_chain(
+                chain,
+                &patterns,
+                &[],
+                &SequencerParams::default(),
+                &settings,
+            )
+            .filter(|event| matches!(event.event, SequencerEvent::NoteOn { .. }))
+            .map(|event| event.pulse_no / bar_pulses)
+            .collect()
+        };
+
+        // A chain that is not looping holds its last entry, including its fill mode.
+        assert_eq!(note_on_bars(&chain), [0, 0, 1, 1]);
+
+        // An empty chain plays the patterns of the sequencer with the fill mode of the
+        // playback parameters.
+        let empty_chain_bars = |fill| -> Vec<_> {
+            let mut sequencer = Sequencer::new();
+            sequencer.patterns_mut().clone_from(&patterns[0]);
+            let params = SequencerParams {
+                fill,
+                ..Default::default()
+            };
+            let empty = Chain::new();
+            Render::new(sequencer, &params, &settings)
+                .with_chain(&empty, &patterns)
+                .filter(|event| matches!(event.event, SequencerEvent::NoteOn { .. }))
+                .map(|event| event.pulse_no / bar_pulses)
+                .collect()
+        };
+        assert_eq!(empty_chain_bars(true), [0, 0, 1, 1]);
+        assert_eq!(empty_chain_bars(false), []);
+    }
+}
@@ -0,0 +1,58 @@
+//! Pseudo random number generator.
+
+/// Small deterministic pseudo random number generator based on xorshift32.
+///
+/// The same seed always produces the same sequence of numbers.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    /// Internal state, never zero.
+    state: u32,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Rng {
+    /// Returns a new instance initialized with `seed`.
+    pub fn new(seed: u32) -> Self {
+        let mut rng = Self { state: 0 };
+        rng.seed(seed);
+        rng
+    }
+
+    /// Restarts the sequence with a new seed.
+    pub fn seed(&mut self, seed: u32) {
+        // Scramble the seed, so that similar seeds result in different sequences.
+        let mut state = seed.wrapping_add(0x9e37_79b9);
+        state = (state ^ (state >> 16)).wrapping_mul(0x85eb_ca6b);
+        state = (state ^ (state >> 13)).wrapping_mul(0xc2b2_ae35);
+        state ^= state >> 16;
+
+        // Xorshift requires a non-zero state.
+        self.state = if state == 0 { 0x9e37_79b9 } else { state };
+    }
+
+    /// Returns the next random number.
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// Returns `true` with a chance of `percent`.
+    ///
+    /// Values of 100 and more always return `true` without advancing the sequence.
+    pub fn chance(&mut self, percent: u8) -> bool {
+        if percent >= 100 {
+            return true;
+        }
+
+        self.next_u32() % 100 < percent as u32
+    }
+}
@@ -0,0 +1,102 @@
+//! Musical scales for pitch quantization.
+
+use serde::{Deserialize, Serialize};
+
+/// Musical scale variants.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum Scale {
+    /// All 12 semitones, no quantization.
+    #[default]
+    Chromatic,
+
+    /// Major scale.
+    Major,
+
+    /// Natural minor scale.
+    Minor,
+
+    /// Harmonic minor scale.
+    HarmonicMinor,
+
+    /// Dorian mode.
+    Dorian,
+
+    /// Phrygian mode.
+    Phrygian,
+
+    /// Major pentatonic scale.
+    PentatonicMajor,
+
+    /// Minor pentatonic scale.
+    PentatonicMinor,
+
+    /// Blues scale.
+    Blues,
+}
+
+impl Scale {
+    /// List of all scales.
+    pub const ALL: [Scale; 9] = [
+        Scale::Chromatic,
+        Scale::Major,
+        Scale::Minor,
+        Scale::HarmonicMinor,
+        Scale::Dorian,
+        Scale::Phrygian,
+        Scale::PentatonicMajor,
+        Scale::PentatonicMinor,
+        Scale::Blues,
+    ];
+
+    /// Returns the intervals of the scale in semitones from the root.
+    pub fn intervals(&self) -> &'static [i32] {
+        match self {
+            Self::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            Self::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Self::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            Self::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11],
+            Self::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            Self::Phrygian => &[0, 1, 3, 5, 7, 8, 10],
+            Self::PentatonicMajor => &[0, 2, 4, 7, 9],
+            Self::PentatonicMinor => &[0, 3, 5, 7, 10],
+            Self::Blues => &[0, 3, 5, 6, 7, 10],
+        }
+    }
+
+    /// Returns a short name of the scale.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Chromatic => "Chromatic",
+            Self::Major => "Major",
+            Self::Minor => "Minor",
+            Self::HarmonicMinor => "Harm. Minor",
+            Self::Dorian => "Dorian",
+            Self::Phrygian => "Phrygian",
+            Self::PentatonicMajor => "Penta Major",
+            Self::PentatonicMinor => "Penta Minor",
+            Self::Blues => "Blues",
+        }
+    }
+
+    /// Returns the note of the scale that is nearest to `note`.
+    /// - `note`: Note number to quantize.
+    /// - `root`: Root note of the scale, only the pitch class is relevant.
+    ///
+    /// If two notes of the scale are at the same distance, the lower one is returned.
+    pub fn quantize(&self, note: i32, root: i32) -> i32 {
+        let relative = note - root.rem_euclid(12);
+        let semitone = relative.rem_euclid(12);
+
+        // The root of the next octave is included as candidate for notes at the top.
+        let nearest = self
+            .intervals()
+            .iter()
+            .copied()
+            .chain(core::iter::once(12))
+            .min_by_key(|interval| (interval - semitone).abs())
+            .unwrap_or(semitone);
+
+        note - semitone + nearest
+    }
+}
@@ -0,0 +1,176 @@
+//! Note off scheduler.
+
+use heapless::Vec;
+
+use crate::params::Pitch;
+
+/// Maximum number of pending note offs per scheduler.
+pub const NOTE_OFF_CAPACITY: usize = 16;
+
+/// Fixed-capacity scheduler for pending note offs, keyed by pitch.
+///
+/// Only one note off per pitch can be pending, so overlapping notes must have
+/// different pitches.
+#[derive(Debug, Default, Clone)]
+pub struct NoteOffScheduler {
+    /// Pending note offs as tuples of (pulse number, pitch).
+    /// A pulse number of `None` holds the note until it is released explicitly.
+    pending: Vec<(Option<u32>, Pitch), NOTE_OFF_CAPACITY>,
+}
+
+impl NoteOffScheduler {
+    /// Returns a new instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedules a note off for `pitch` at `pulse_no`, `None` holds the note.
+    /// - `now`: Current pulse number, used to determine the note that ends first.
+    ///
+    /// If the scheduler is full, the note that would end first is removed and
+    /// its pitch is returned, so it can be stopped immediately.
+    pub fn schedule(&mut self, pulse_no: Option<u32>, pitch: Pitch, now: u32) -> Option<Pitch> {
+        let mut evicted = None;
+
+        if self.pending.is_full() {
+            let first = self
+                .pending
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, (pulse_no, _))| {
+                    pulse_no.map_or(u32::MAX, |pulse_no| pulse_no.wrapping_sub(now))
+                })
+                .map(|(index, _)| index);
+
+            if let Some(index) = first {
+                evicted = Some(self.pending.swap_remove(index).1);
+            }
+        }
+
+        self.pending.push((pulse_no, pitch)).ok();
+
+        evicted
+    }
+
+    /// Removes a pending note off for `pitch` and returns if it was pending.
+    pub fn take(&mut self, pitch: Pitch) -> bool {
+        if let Some(index) = self.pending.iter().position(|(_, p)| *p == pitch) {
+            self.pending.swap_remove(index);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes a note off that is due at `pulse_no` and returns its pitch.
+    pub fn next_due(&mut self, pulse_no: u32) -> Option<Pitch> {
+        let index = self
+            .pending
+            .iter()
+            .position(|(p, _)| *p == Some(pulse_no))?;
+        Some(self.pending.swap_remove(index).1)
+    }
+
+    /// Removes a held note and returns its pitch.
+    pub fn next_held(&mut self) -> Option<Pitch> {
+        let index = self.pending.iter().position(|(p, _)| p.is_none())?;
+        Some(self.pending.swap_remove(index).1)
+    }
+
+    /// Removes any pending note off and returns its pitch.
+    pub fn next_pending(&mut self) -> Option<Pitch> {
+        self.pending.pop().map(|(_, pitch)| pitch)
+    }
+
+    /// Returns if no note offs are pending.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a scheduler filled with note offs for the pitches 0 to 15 at `pulse_no(pitch)`.
+    fn full(pulse_no: impl Fn(i32) -> Option<u32>) -> NoteOffScheduler {
+        let mut scheduler = NoteOffScheduler::new();
+        for pitch in 0..NOTE_OFF_CAPACITY as i32 {
+            assert_eq!(
+                scheduler.schedule(pulse_no(pitch), Pitch::Custom(pitch), 0),
+                None
+            );
+        }
+        scheduler
+    }
+
+    #[test]
+    fn full_scheduler_evicts_the_note_that_ends_first() {
+        let mut scheduler = full(|pitch| Some(100 - pitch as u32));
+        let last = Pitch::Custom(NOTE_OFF_CAPACITY as i32 - 1);
+        assert_eq!(
+            scheduler.schedule(Some(200), Pitch::Custom(100), 50),
+            Some(last)
+        );
+        assert!(!scheduler.take(last));
+        assert!(scheduler.take(Pitch::Custom(100)));
+    }
+
+    #[test]
+    fn eviction_handles_wrapping_pulse_numbers() {
+        let now = u32::MAX - 1;
+        let mut scheduler = full(|pitch| Some(now.wrapping_add(2 + pitch as u32)));
+        assert!(scheduler.take(Pitch::Custom(0)));
+        scheduler.schedule(Some(u32::MAX), Pitch::Custom(100), now);
+
+        // The note off after the wrap ends later than the one before it.
+        assert_eq!(
+            scheduler.schedule(Some(10), Pitch::Custom(101), now),
+            Some(Pitch::Custom(100))
+        );
+    }
+
+    #[test]
+    fn held_notes_are_evicted_last() {
+        let mut scheduler = full(|pitch| (pitch == 7).then_some(1000));
+        assert_eq!(
+            scheduler.schedule(None, Pitch::Custom(100), 0),
+            Some(Pitch::Custom(7))
+        );
+
+        let evicted = scheduler.schedule(None, Pitch::Custom(101), 0);
+        assert!(matches!(evicted, Some(Pitch::Custom(_))));
+        assert!(!scheduler.take(evicted.unwrap()));
+    }
+
+    #[test]
+    fn due_and_held_notes_are_separate() {
+        let mut scheduler = NoteOffScheduler::new();
+        scheduler.schedule(None, Pitch::Custom(1), 0);
+        scheduler.schedule(Some(5), Pitch::Custom(2), 0);
+        scheduler.schedule(Some(6), Pitch::Custom(3), 0);
+
+        assert_eq!(scheduler.next_due(4), None);
+        assert_eq!(scheduler.next_due(5), Some(Pitch::Custom(2)));
+        assert_eq!(scheduler.next_due(5), None);
+
+        assert_eq!(scheduler.next_held(), Some(Pitch::Custom(1)));
+        assert_eq!(scheduler.next_held(), None);
+
+        assert!(!scheduler.is_empty());
+        assert_eq!(scheduler.next_pending(), Some(Pitch::Custom(3)));
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn take_removes_only_the_given_pitch() {
+        let mut scheduler = NoteOffScheduler::new();
+        scheduler.schedule(Some(5), Pitch::Default, 0);
+        scheduler.schedule(None, Pitch::Custom(1), 0);
+
+        assert!(scheduler.take(Pitch::Custom(1)));
+        assert!(!scheduler.take(Pitch::Custom(1)));
+        assert_eq!(scheduler.next_held(), None);
+        assert_eq!(scheduler.next_due(5), Some(Pitch::Default));
+    }
+}
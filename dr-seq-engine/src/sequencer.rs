@@ -0,0 +1,527 @@
+//! Multi-track sequencer.
+
+use heapless::spsc::Queue;
+
+use crate::params::{Gate, Pitch, Velocity};
+use crate::pattern::Pattern;
+use crate::step::StepEvent;
+use crate::track::{Track, TrackEvent, TrackParams};
+
+/// Capacity of the event queue.
+pub const SEQUENCER_QUEUE_CAPACITY: usize = 256;
+
+/// Event queue.
+type EventQueue = Queue<SequencerEvent, SEQUENCER_QUEUE_CAPACITY>;
+
+/// Sequencer playing a pattern on each of its tracks.
+///
+/// The tracks are tied together by the manglers, the accent track, the choke groups and
+/// the velocity settings. Each track can also be accented by its own accent lane. The
+/// generated note events are independent of MIDI, so the sequencer can be used by a
+/// plugin, a firmware or an offline renderer alike.
+#[derive(Debug)]
+pub struct Sequencer<const NUM_TRACKS: usize, const NUM_STEPS: usize> {
+    /// Individual tracks.
+    tracks: [Track; NUM_TRACKS],
+
+    /// Patterns for the tracks.
+    patterns: [Pattern<NUM_STEPS>; NUM_TRACKS],
+
+    /// Step repeats for the tracks, set by the FLAME mangler.
+    step_repeats: [bool; NUM_TRACKS],
+
+    /// Order in which the tracks are considered when only one of them can repeat a step.
+    repeat_priority: [usize; NUM_TRACKS],
+
+    /// Queue for generated events.
+    event_queue: EventQueue,
+}
+
+impl<const NUM_TRACKS: usize, const NUM_STEPS: usize> Default for Sequencer<NUM_TRACKS, NUM_STEPS> {
+    fn default() -> Self {
+        Self {
+            tracks: core::array::from_fn(|_| Track::new()),
+            patterns: core::array::from_fn(|_| Pattern::new()),
+            step_repeats: [false; NUM_TRACKS],
+            repeat_priority: core::array::from_fn(|n| n),
+            event_queue: EventQueue::new(),
+        }
+    }
+}
+
+impl<const NUM_TRACKS: usize, const NUM_STEPS: usize> Sequencer<NUM_TRACKS, NUM_STEPS> {
+    /// Returns a new instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the patterns of the tracks.
+    pub fn patterns(&self) -> &[Pattern<NUM_STEPS>; NUM_TRACKS] {
+        &self.patterns
+    }
+
+    /// Returns the patterns of the tracks as mutable.
+    pub fn patterns_mut(&mut self) -> &mut [Pattern<NUM_STEPS>; NUM_TRACKS] {
+        &mut self.patterns
+    }
+
+    /// Sets the order in which the tracks are considered for step repeats.
+    ///
+    /// Tracks missing in `priority` are considered last in their natural order.
+    pub fn set_repeat_priority(&mut self, priority: &[usize]) {
+        let mut order = priority
+            .iter()
+            .copied()
+            .filter(|n| *n < NUM_TRACKS)
+            .chain(0..NUM_TRACKS);
+
+        let mut repeat_priority = [0; NUM_TRACKS];
+        for slot in 0..NUM_TRACKS {
+            let track = order
+                .by_ref()
+                .find(|n| !repeat_priority[..slot].contains(n))
+                .unwrap_or(slot);
+            repeat_priority[slot] = track;
+        }
+
+        self.repeat_priority = repeat_priority;
+    }
+
+    /// Restarts the playback, e.g. when the transport starts.
+    ///
+    /// Each track gets its own seed derived from `seed`, so the random sequences for the
+    /// probabilities are reproducible but differ between the tracks.
+    pub fn start(&mut self, seed: u32) {
+        for (n, track) in self.tracks.iter_mut().enumerate() {
+            track.set_seed(seed.wrapping_mul(NUM_TRACKS as u32).wrapping_add(n as u32));
+            track.reset();
+        }
+    }
+
+    /// Stops the playback and enqueues note offs for all sounding notes.
+    pub fn stop(&mut self) {
+        for n in 0..NUM_TRACKS {
+            self.tracks[n].flush();
+            self.enqueue_note_offs(n);
+        }
+    }
+
+    /// Moves the note offs of a track into the event queue, other events are dropped.
+    fn enqueue_note_offs(&mut self, n: usize) {
+        while let Some(event) = self.tracks[n].next_event() {
+            if let TrackEvent::StepEvent(_, _, StepEvent::NoteOff { pitch }) = event {
+                self.event_queue
+                    .enqueue(SequencerEvent::NoteOff { track: n, pitch })
+                    .ok();
+            }
+        }
+    }
+
+    /// Ends the sounding notes of the other tracks in the choke group of a track.
+    ///
+    /// The events of the other tracks have already been dequeued at this point,
+    /// so only their note offs are enqueued.
+    fn choke(&mut self, n: usize, tracks: &[TrackSettings; NUM_TRACKS]) {
+        let Some(group) = tracks[n].choke_group else {
+            return;
+        };
+
+        for (m, settings) in tracks.iter().enumerate() {
+            if m != n && settings.choke_group == Some(group) {
+                self.tracks[m].release_notes();
+                self.enqueue_note_offs(m);
+            }
+        }
+    }
+
+    /// Updates all tracks when a clock pulse occurs.
+    /// - `pulse_no`: Number of the pulse, counted from the start of the song.
+    /// - `ppq`: Pulses per quarter note of the clock.
+    /// - `params`: Parameters for the playback.
+    pub fn update(&mut self, pulse_no: u32, ppq: u32, params: &SequencerParams<NUM_TRACKS>) {
+        let manglers = &params.manglers;
+        let track_ppq = manglers.ppq(ppq);
+
+        let mut track_params = TrackParams {
+            swing: params.swing * ppq as i32 / 8 / 100,
+            shift: if manglers.swag { 1 } else { 0 },
+            reverse: manglers.mirror,
+            triplet: params.triplet,
+            fill: params.fill,
+            ..Default::default()
+        };
+
+        // The HACK mangler re-sorts the order of the steps.
+        if manglers.hack {
+            track_params.resort_fn = Some(hack_resort);
+        }
+
+        // Track that played the first note while the SOLE mangler is enabled.
+        // Layered notes of the same step are still played.
+        let mut sole_track = None;
+
+        for n in 0..NUM_TRACKS {
+            let settings = &params.tracks[n];
+
+            // The accent track only modifies the velocities of the other tracks.
+            track_params.enable = settings.enable && params.accent_track != Some(n);
+            track_params.delay = settings.delay;
+            track_params.gate = Gate::Length(settings.gate);
+            track_params.repeat = manglers.flame && self.step_repeats[n];
+
+            if !track_params.enable {
+                // Clear step repeats on disabled tracks.
+                self.step_repeats[n] = false;
+            }
+
+            self.tracks[n].update(pulse_no, track_ppq, &self.patterns[n], &track_params);
+
+            while let Some(event) = self.tracks[n].next_event() {
+                // Hits of ratcheted steps are played like regular steps,
+                // but with a scaled velocity.
+                let (event, velocity_scale) = match event {
+                    TrackEvent::RatchetEvent(step, step_count, step_event, scale) => (
+                        TrackEvent::StepEvent(step, step_count, step_event),
+                        scale as f32 / 100.0,
+                    ),
+                    event => (event, 1.0),
+                };
+
+                match event {
+                    TrackEvent::StepEvent(step, step_count, StepEvent::NoteOn { pitch, vel })
+                        if sole_track.is_none_or(|track| track == n) =>
+                    {
+                        // The accent track runs with its own length, so its step is
+                        // determined from the step count of the note, which can be
+                        // nudged away from the current step.
+                        let accent = params
+                            .accent_track
+                            .and_then(|accent_track| self.patterns.get(accent_track))
+                            .is_some_and(|pattern| {
+                                Track::step_of(step_count, pattern.length(), &track_params)
+                                    .is_some_and(|step| pattern.step(step).enabled())
+                            });
+
+                        // Steps set on the accent lane of the track use its own accent velocity.
+                        let lane_accent = settings
+                            .accent_lane
+                            .filter(|_| self.patterns[n].step(step).accent());
+                        let velocities = match lane_accent {
+                            Some(accent) => VelocityParams {
+                                accent,
+                                ..params.velocities
+                            },
+                            None => params.velocities,
+                        };
+                        let accent = accent || lane_accent.is_some();
+                        let velocity = velocity_scale * velocities.velocity(vel, accent);
+                        self.choke(n, &params.tracks);
+                        self.event_queue
+                            .enqueue(SequencerEvent::NoteOn {
+                                track: n,
+                                pitch,
+                                velocity,
+                            })
+                            .ok();
+                        if manglers.sole {
+                            sole_track = Some(n);
+                        }
+                        if manglers.flame {
+                            self.step_repeats[n] = true;
+                        }
+                    }
+                    TrackEvent::StepEvent(_, _, StepEvent::NoteOff { pitch }) => {
+                        self.event_queue
+                            .enqueue(SequencerEvent::NoteOff { track: n, pitch })
+                            .ok();
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // Make sure only one track has repeat enabled.
+        let mut disable_repeat = false;
+        for n in self.repeat_priority {
+            if disable_repeat {
+                self.step_repeats[n] = false;
+            } else if self.step_repeats[n] {
+                disable_repeat = true;
+            }
+        }
+    }
+
+    /// Returns the next event.
+    pub fn next_event(&mut self) -> Option<SequencerEvent> {
+        self.event_queue.dequeue()
+    }
+}
+
+/// Re-sorts the steps for the HACK mangler.
+fn hack_resort(step: u32) -> u32 {
+    let base = step / 8 * 8;
+    let sub = step % 8;
+    base + match sub {
+        0 => 0,
+        1 => 3,
+        2 => 1,
+        3 => 7,
+        4 => 2,
+        5 => 6,
+        6 => 4,
+        7 => 5,
+        _ => 0,
+    }
+}
+
+/// Sequencer playback parameters.
+#[derive(Debug, Clone)]
+pub struct SequencerParams<const NUM_TRACKS: usize> {
+    /// Settings of the individual tracks.
+    pub tracks: [TrackSettings; NUM_TRACKS],
+
+    /// Swing in percent of a 1/32 note.
+    pub swing: i32,
+
+    /// Triplet mode.
+    pub triplet: bool,
+
+    /// Fill mode for trigger conditions.
+    pub fill: bool,
+
+    /// Manglers modifying the playback.
+    pub manglers: Manglers,
+
+    /// Velocities for the step states.
+    pub velocities: VelocityParams,
+
+    /// Number of the track used for global accent.
+    pub accent_track: Option<usize>,
+}
+
+impl<const NUM_TRACKS: usize> Default for SequencerParams<NUM_TRACKS> {
+    fn default() -> Self {
+        Self {
+            tracks: [TrackSettings::default(); NUM_TRACKS],
+            swing: 0,
+            triplet: false,
+            fill: false,
+            manglers: Manglers::default(),
+            velocities: VelocityParams::default(),
+            accent_track: None,
+        }
+    }
+}
+
+/// Settings of a single track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackSettings {
+    /// Enable the playback.
+    pub enable: bool,
+
+    /// Time delay in pulses.
+    pub delay: i32,
+
+    /// Note length in percent of a step for steps without an individual setting.
+    pub gate: u16,
+
+    /// Choke group, a note of the track ends the sounding notes of the other tracks
+    /// in the same group, like an open hihat is cut by a closed one.
+    pub choke_group: Option<u8>,
+
+    /// Accent velocity of the accent lane of the track, which is off if not set.
+    ///
+    /// Steps set on the lane are accented in addition to the accent track, with this
+    /// velocity instead of the global one.
+    pub accent_lane: Option<u8>,
+}
+
+impl Default for TrackSettings {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            delay: 0,
+            gate: 50,
+            choke_group: None,
+            accent_lane: None,
+        }
+    }
+}
+
+/// Manglers modifying the playback of all tracks.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Manglers {
+    /// Double the speed.
+    pub fast: bool,
+
+    /// Halve the speed.
+    pub slow: bool,
+
+    /// Shift the steps by one.
+    pub swag: bool,
+
+    /// Reverse the playback direction.
+    pub mirror: bool,
+
+    /// Re-sort the order of the steps.
+    pub hack: bool,
+
+    /// Play only the first track that triggers a note on a step.
+    pub sole: bool,
+
+    /// Repeat the last played step of one track on empty steps.
+    pub flame: bool,
+}
+
+impl Manglers {
+    /// Returns the pulses per quarter note the tracks are updated with.
+    /// - `ppq`: Pulses per quarter note of the clock.
+    pub fn ppq(&self, ppq: u32) -> u32 {
+        let mut track_ppq = ppq;
+
+        // The FAST mangler doubles the speed by halving the ppq.
+        if self.fast {
+            track_ppq /= 2;
+        }
+
+        // The SLOW mangler halves the speed by doubling the ppq.
+        if self.slow {
+            track_ppq *= 2;
+        }
+
+        track_ppq
+    }
+}
+
+/// Velocities for the step states, in the range of 0-127.
+#[derive(Debug, Clone, Copy)]
+pub struct VelocityParams {
+    /// Velocity for normal steps.
+    pub normal: u8,
+
+    /// Velocity for accented steps.
+    pub accent: u8,
+
+    /// Use the accent velocity as absolute value instead of adding it to the normal one.
+    pub accent_absolute: bool,
+
+    /// Velocity for weak steps.
+    pub weak: u8,
+
+    /// Velocity for ghost steps.
+    pub ghost: u8,
+}
+
+impl Default for VelocityParams {
+    fn default() -> Self {
+        Self {
+            normal: 100,
+            accent: 27,
+            accent_absolute: false,
+            weak: 60,
+            ghost: 20,
+        }
+    }
+}
+
+impl VelocityParams {
+    /// Returns the velocity in the range of 0.0-1.0.
+    /// - `vel`: Velocity of the step event.
+    /// - `accent`: Flag if the accent track or the accent lane is set on the step.
+    pub fn velocity(&self, vel: Velocity, accent: bool) -> f32 {
+        // Convert the velocity values from 0-127 into 0.0-1.0 range.
+        let normal = self.normal.min(127) as f32 / 127.0;
+        let accent_velocity = if self.accent_absolute {
+            self.accent.min(127) as f32 / 127.0
+        } else {
+            (normal + self.accent.min(127) as f32 / 127.0).clamp(0.0, 1.0)
+        };
+
+        match vel {
+            Velocity::Accent => accent_velocity,
+            Velocity::Weak => self.weak.min(127) as f32 / 127.0,
+            Velocity::Ghost => self.ghost.min(127) as f32 / 127.0,
+            Velocity::Custom(velocity) => velocity.min(127) as f32 / 127.0,
+            _ => {
+                if accent {
+                    accent_velocity
+                } else {
+                    normal
+                }
+            }
+        }
+    }
+}
+
+/// Events generated by the sequencer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum SequencerEvent {
+    /// Start of a note.
+    NoteOn {
+        /// Number of the track.
+        track: usize,
+
+        /// Pitch of the note.
+        pitch: Pitch,
+
+        /// Velocity in the range of 0.0-1.0.
+        velocity: f32,
+    },
+
+    /// End of a note.
+    NoteOff {
+        /// Number of the track.
+        track: usize,
+
+        /// Pitch of the note.
+        pitch: Pitch,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the velocity of the note on step 5 of track 1, which is nudged to an earlier
+    /// time, with the accent track set on `accent_step`.
+    fn nudged_velocity(accent_step: usize) -> f32 {
+        let mut sequencer = Sequencer::<2, 16>::new();
+        let step = &mut sequencer.patterns_mut()[0].steps_mut()[4];
+        step.enable();
+        step.set_event(Some(StepEvent::NoteOn {
+            pitch: Pitch::Default,
+            vel: Velocity::Default,
+        }));
+        step.set_nudge(-3);
+        sequencer.patterns_mut()[1].steps_mut()[accent_step].enable();
+
+        let params = SequencerParams {
+            accent_track: Some(1),
+            ..Default::default()
+        };
+
+        sequencer.start(0);
+        for pulse_no in 0..48 {
+            sequencer.update(pulse_no, 24, &params);
+            while let Some(event) = sequencer.next_event() {
+                if let SequencerEvent::NoteOn { velocity, .. } = event {
+                    // The step starts at pulse 24, the nudge moves it into step 4.
+                    assert_eq!(pulse_no, 21);
+                    return velocity;
+                }
+            }
+        }
+        panic!("note not played");
+    }
+
+    #[test]
+    fn accent_follows_the_nudged_step() {
+        let velocities = VelocityParams::default();
+        let accent = velocities.velocity(Velocity::Default, true);
+        let normal = velocities.velocity(Velocity::Default, false);
+
+        assert_eq!(nudged_velocity(4), accent);
+        assert_eq!(nudged_velocity(3), normal);
+    }
+}
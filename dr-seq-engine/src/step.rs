@@ -1,27 +1,73 @@
 //! Sequencer step.
 
+use heapless::Vec;
 use serde::{Deserialize, Serialize};
 
-use crate::params::{Pitch, Velocity};
+use crate::params::{Condition, Gate, Pitch, Ramp, Velocity};
+
+/// Maximum number of ratchet hits within a step.
+pub const MAX_RATCHETS: u8 = 8;
+
+/// Maximum number of events of a step.
+pub const MAX_STEP_EVENTS: usize = 4;
+
+/// Fixed-capacity list of step events.
+pub type StepEvents = Vec<StepEvent, MAX_STEP_EVENTS>;
 
 /// Sequencer step.
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Step {
     /// Flag if step is enabled for playing.
     enabled: bool,
 
-    /// Event for this step.
-    event: Option<StepEvent>,
+    /// Events for this step, played simultaneously.
+    events: StepEvents,
+
+    /// Condition that must be met to trigger the step.
+    condition: Condition,
+
+    /// Trigger probability in percent.
+    probability: u8,
+
+    /// Note length, overrides the track setting if set.
+    gate: Option<Gate>,
+
+    /// Timing offset in clock pulses, limited to half a step in both directions.
+    nudge: i32,
+
+    /// Number of evenly spaced hits within the step.
+    ratchets: u8,
+
+    /// Velocity ramp over the ratchet hits.
+    ramp: Ramp,
+
+    /// Flag if the step is set on the accent lane of its track.
+    accent: bool,
+}
+
+impl Default for Step {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            events: StepEvents::new(),
+            condition: Condition::default(),
+            probability: 100,
+            gate: None,
+            nudge: 0,
+            ratchets: 1,
+            ramp: Ramp::default(),
+            accent: false,
+        }
+    }
 }
 
 impl Step {
     /// Returns a new step with a specific event.
     pub fn new(event: Option<StepEvent>) -> Self {
-        Self {
-            event,
-            ..Default::default()
-        }
+        let mut step = Self::default();
+        step.set_event(event);
+        step
     }
 
     /// Enables the step.
@@ -39,19 +85,106 @@ impl Step {
         self.enabled
     }
 
-    /// Sets a new event.
+    /// Sets a single event, replacing all existing events.
     pub fn set_event(&mut self, event: Option<StepEvent>) {
-        self.event = event;
+        self.events.clear();
+        if let Some(event) = event {
+            self.events.push(event).ok();
+        }
+    }
+
+    /// Returns the first event.
+    pub fn event(&self) -> Option<&StepEvent> {
+        self.events.first()
+    }
+
+    /// Adds an event to be played together with the existing ones.
+    ///
+    /// Returns the event back as error if the step already holds `MAX_STEP_EVENTS` events.
+    pub fn add_event(&mut self, event: StepEvent) -> Result<(), StepEvent> {
+        self.events.push(event)
+    }
+
+    /// Returns a shared reference to the events.
+    pub fn events(&self) -> &StepEvents {
+        &self.events
+    }
+
+    /// Returns a mutable reference to the events.
+    pub fn events_mut(&mut self) -> &mut StepEvents {
+        &mut self.events
+    }
+
+    /// Sets the trigger condition.
+    pub fn set_condition(&mut self, condition: Condition) {
+        self.condition = condition;
+    }
+
+    /// Returns the trigger condition.
+    pub fn condition(&self) -> Condition {
+        self.condition
+    }
+
+    /// Sets the trigger probability in percent, limited to a maximum of 100.
+    pub fn set_probability(&mut self, probability: u8) {
+        self.probability = probability.min(100);
+    }
+
+    /// Returns the trigger probability in percent.
+    pub fn probability(&self) -> u8 {
+        self.probability
+    }
+
+    /// Sets the note length, `None` uses the track setting.
+    pub fn set_gate(&mut self, gate: Option<Gate>) {
+        self.gate = gate;
+    }
+
+    /// Returns the note length if set for this step.
+    pub fn gate(&self) -> Option<Gate> {
+        self.gate
+    }
+
+    /// Sets the timing offset in clock pulses.
+    ///
+    /// Negative values move the step earlier, positive values later.
+    pub fn set_nudge(&mut self, nudge: i32) {
+        self.nudge = nudge;
+    }
+
+    /// Returns the timing offset in clock pulses.
+    pub fn nudge(&self) -> i32 {
+        self.nudge
+    }
+
+    /// Sets the number of hits within the step, limited to the range from 1 to `MAX_RATCHETS`.
+    pub fn set_ratchets(&mut self, ratchets: u8) {
+        self.ratchets = ratchets.clamp(1, MAX_RATCHETS);
+    }
+
+    /// Returns the number of hits within the step.
+    pub fn ratchets(&self) -> u8 {
+        self.ratchets
+    }
+
+    /// Sets the velocity ramp over the ratchet hits.
+    pub fn set_ramp(&mut self, ramp: Ramp) {
+        self.ramp = ramp;
+    }
+
+    /// Returns the velocity ramp over the ratchet hits.
+    pub fn ramp(&self) -> Ramp {
+        self.ramp
     }
 
-    /// Returns a shared reference to the event.
-    pub fn event(&self) -> &Option<StepEvent> {
-        &self.event
+    /// Sets the step on the accent lane of its track.
+    pub fn set_accent(&mut self, accent: bool) {
+        self.accent = accent;
     }
 
-    /// Returns a mutable reference to the event.
-    pub fn event_mut(&mut self) -> &mut Option<StepEvent> {
-        &mut self.event
+    /// Returns if the step is set on the accent lane of its track.
+    pub fn accent(&self) -> bool {
+        self.accent
     }
 }
 
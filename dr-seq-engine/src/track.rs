@@ -1,12 +1,16 @@
 //! Sequencer track.
 
+use heapless::Vec;
 use heapless::spsc::Queue;
 
-use crate::params::Pitch;
-use crate::step::{Step, StepEvent};
+use crate::params::{Gate, Pitch};
+use crate::pattern::Pattern;
+use crate::rng::Rng;
+use crate::scheduler::NoteOffScheduler;
+use crate::step::{MAX_RATCHETS, Step, StepEvent, StepEvents};
 
 /// Capacity of the event queue.
-pub const EVENT_QUEUE_CAPACITY: usize = 16;
+pub const EVENT_QUEUE_CAPACITY: usize = 32;
 
 /// Event queue.
 type EventQueue = Queue<TrackEvent, EVENT_QUEUE_CAPACITY>;
@@ -17,11 +21,29 @@ pub struct Track {
     /// Last played step number.
     play_step: Option<u32>,
 
+    /// Step count of the last played step, counted from the start of the song.
+    play_step_count: u32,
+
+    /// Number of the next step to be triggered, counted from the start of the song.
+    next_step_count: Option<u32>,
+
     /// Monotonic pulse count. Used for note off scheduling.
     pulse_count: u32,
 
-    /// Pulse number and pitch for next note off event.
-    scheduled_note_off: Option<(u32, Pitch)>,
+    /// Scheduler for the note offs of sounding notes.
+    note_offs: NoteOffScheduler,
+
+    /// Pending hits of a ratcheted step.
+    ratchet_hits: Vec<RatchetHit, { MAX_RATCHETS as usize }>,
+
+    /// Loop number of the first loop played after a reset.
+    loop_origin: Option<u32>,
+
+    /// Seed for the random number generator.
+    seed: u32,
+
+    /// Random number generator for the step probabilities.
+    rng: Rng,
 
     /// Queue for generated events.
     event_queue: EventQueue,
@@ -32,8 +54,14 @@ impl Clone for Track {
     fn clone(&self) -> Self {
         Self {
             play_step: None,
+            play_step_count: 0,
+            next_step_count: None,
             pulse_count: 0,
-            scheduled_note_off: None,
+            note_offs: NoteOffScheduler::new(),
+            ratchet_hits: Vec::new(),
+            loop_origin: None,
+            seed: self.seed,
+            rng: Rng::new(self.seed),
             event_queue: EventQueue::new(),
         }
     }
@@ -46,7 +74,254 @@ impl Track {
     }
 
     /// Updates the track when a clock pulse occurs.
-    pub fn update(&mut self, pulse_no: u32, ppq: u32, steps: &[Step], params: &TrackParams) {
+    ///
+    /// The playback wraps around at the active length of the pattern.
+    pub fn update<const NUM_STEPS: usize>(
+        &mut self,
+        pulse_no: u32,
+        ppq: u32,
+        pattern: &Pattern<NUM_STEPS>,
+        params: &TrackParams,
+    ) {
+        let steps = pattern.active_steps();
+        let length = steps.len() as u32;
+
+        // Check if previously started notes have reached their length.
+        let step_no = Self::step_at(pulse_no, ppq, length, params)
+            .or(self.play_step)
+            .unwrap_or_default();
+        while let Some(pitch) = self.note_offs.next_due(self.pulse_count) {
+            self.enqueue_note_off(step_no, pitch);
+        }
+
+        self.play_ratchet_hits();
+
+        if params.enable && length > 0 {
+            let step_count = Self::step_count(pulse_no, ppq, params);
+
+            // The next step is usually the current one or one after if the current step
+            // has already been triggered. A step can also still be pending, when it is
+            // nudged to a later time. Otherwise the position has jumped and the playback
+            // continues from the current step.
+            let mut next_step_count = match self.next_step_count {
+                Some(next) if next + 1 >= step_count && next <= step_count + 2 => next,
+                _ => step_count,
+            };
+
+            // Trigger all steps that have reached their time, including the
+            // step after the current one, which can be nudged to an earlier time.
+            while next_step_count <= step_count.saturating_add(1) {
+                let Some((loop_no, play_step)) = Self::map_step(next_step_count, length, params)
+                else {
+                    if next_step_count > step_count {
+                        break;
+                    }
+
+                    // Steps without a mapping are skipped once they are reached.
+                    next_step_count += 1;
+                    continue;
+                };
+
+                let nudge = steps[play_step as usize].nudge();
+                let max_nudge = (ppq / 8) as i32;
+                let trigger_pulse = Self::step_start(next_step_count, ppq, params)
+                    + nudge.clamp(-max_nudge, max_nudge) as i64;
+
+                if trigger_pulse > pulse_no as i64 {
+                    break;
+                }
+
+                self.trigger_step(steps, next_step_count, loop_no, play_step, ppq, params);
+                next_step_count += 1;
+            }
+
+            self.next_step_count = Some(next_step_count);
+        } else {
+            self.next_step_count = None;
+        }
+
+        // Pulse count is allowed to overflow explicitly.
+        // But it can take a while until that happens.
+        self.pulse_count = self.pulse_count.wrapping_add(1);
+    }
+
+    /// Triggers the events of a step.
+    /// - `step_count`: Number of the step, counted from the start of the song.
+    fn trigger_step(
+        &mut self,
+        steps: &[Step],
+        step_count: u32,
+        loop_no: u32,
+        play_step: u32,
+        ppq: u32,
+        params: &TrackParams,
+    ) {
+        let mut step = &steps[play_step as usize];
+
+        if params.repeat
+            && !step.enabled()
+            && let Some(last_step) = self.play_step()
+            && let Some(last_step) = steps.get(last_step as usize)
+        {
+            step = last_step;
+        }
+
+        self.play_step = Some(play_step);
+        self.play_step_count = step_count;
+
+        // Loops are counted relative to the first one played after a reset.
+        let loop_origin = *self.loop_origin.get_or_insert(loop_no);
+        let loop_no = loop_no.wrapping_sub(loop_origin);
+
+        // Get the events and emit them.
+        // The probability is checked last, so the random sequence only
+        // advances for steps that would be played otherwise.
+        if step.enabled()
+            && !step.events().is_empty()
+            && step.condition().is_met(loop_no, params.fill)
+            && self.rng.chance(step.probability())
+        {
+            // Ratchets divide the step into evenly spaced hits.
+            let hits = step.ratchets().max(1) as u32;
+            let hit_length = (ppq / 4 / hits).max(1);
+
+            // The note length of the step or the track applies to each hit.
+            let gate = step.gate().unwrap_or(params.gate);
+            let note_length = gate.pulses(hit_length);
+
+            // Remaining hits of a previous step are dropped.
+            self.ratchet_hits.clear();
+
+            if hits == 1 {
+                self.play_events(play_step, step_count, step.events(), note_length, None);
+            } else {
+                for hit in 0..hits {
+                    let scale = step.ramp().scale(hit, hits);
+                    if hit == 0 {
+                        self.play_events(
+                            play_step,
+                            step_count,
+                            step.events(),
+                            note_length,
+                            Some(scale),
+                        );
+                    } else {
+                        let ratchet_hit = RatchetHit {
+                            pulse_no: self.pulse_count.wrapping_add(hit * hit_length),
+                            step_no: play_step,
+                            step_count,
+                            step_events: step.events().clone(),
+                            note_length,
+                            scale,
+                        };
+                        self.ratchet_hits.push(ratchet_hit).ok();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Plays the due hits of a ratcheted step.
+    fn play_ratchet_hits(&mut self) {
+        while let Some(index) = self
+            .ratchet_hits
+            .iter()
+            .position(|hit| hit.pulse_no == self.pulse_count)
+        {
+            let hit = self.ratchet_hits.swap_remove(index);
+            self.play_events(
+                hit.step_no,
+                hit.step_count,
+                &hit.step_events,
+                hit.note_length,
+                Some(hit.scale),
+            );
+        }
+    }
+
+    /// Enqueues the events of a step and schedules the note offs for note on events.
+    /// - `step_count`: Number of the step, counted from the start of the song.
+    /// - `note_length`: Length in pulses or `None` for a tied note.
+    /// - `scale`: Velocity scale in percent for ratchet hits.
+    fn play_events(
+        &mut self,
+        step_no: u32,
+        step_count: u32,
+        step_events: &[StepEvent],
+        note_length: Option<u32>,
+        scale: Option<u8>,
+    ) {
+        // Tied notes are held until the next note starts. This is done once
+        // before all events, so layered notes do not release each other.
+        if step_events
+            .iter()
+            .any(|step_event| matches!(step_event, StepEvent::NoteOn { .. }))
+        {
+            while let Some(pitch) = self.note_offs.next_held() {
+                self.enqueue_note_off(step_no, pitch);
+            }
+        }
+
+        for step_event in step_events {
+            self.play_event(step_no, step_count, step_event.clone(), note_length, scale);
+        }
+    }
+
+    /// Enqueues an event and schedules the note off for note on events.
+    /// - `step_count`: Number of the step, counted from the start of the song.
+    /// - `note_length`: Length in pulses or `None` for a tied note.
+    /// - `scale`: Velocity scale in percent for ratchet hits.
+    fn play_event(
+        &mut self,
+        step_no: u32,
+        step_count: u32,
+        step_event: StepEvent,
+        note_length: Option<u32>,
+        scale: Option<u8>,
+    ) {
+        // If the event is a note on, then a corresponding note off
+        // is scheduled for later processing.
+        if let StepEvent::NoteOn { pitch, .. } = step_event {
+            // If a note with the same pitch is still playing,
+            // it must be stopped before triggering a new one.
+            if self.note_offs.take(pitch) {
+                self.enqueue_note_off(step_no, pitch);
+            }
+
+            let note_off_pulse = note_length.map(|pulses| self.pulse_count.wrapping_add(pulses));
+            if let Some(pitch) = self
+                .note_offs
+                .schedule(note_off_pulse, pitch, self.pulse_count)
+            {
+                // Scheduler was full, so the evicted note is stopped now.
+                self.enqueue_note_off(step_no, pitch);
+            }
+        }
+
+        // Enqueue the event.
+        let track_event = match scale {
+            Some(scale) => TrackEvent::RatchetEvent(step_no, step_count, step_event, scale),
+            None => TrackEvent::StepEvent(step_no, step_count, step_event),
+        };
+        self.event_queue.enqueue(track_event).ok();
+    }
+
+    /// Returns the step number that is played at a pulse for a pattern of `length` steps.
+    ///
+    /// Returns `None` if the pulse does not map to a playable step, e.g. on every 4th step
+    /// in triplet mode or if the length is zero.
+    pub fn step_at(pulse_no: u32, ppq: u32, length: u32, params: &TrackParams) -> Option<u32> {
+        Self::step_of(Self::step_count(pulse_no, ppq, params), length, params)
+    }
+
+    /// Returns the step number that is played at a step count for a pattern of `length`
+    /// steps, see `step_at()`.
+    pub fn step_of(step_count: u32, length: u32, params: &TrackParams) -> Option<u32> {
+        Self::map_step(step_count, length, params).map(|(_, step)| step)
+    }
+
+    /// Returns the number of steps since the start of the song at a pulse.
+    fn step_count(pulse_no: u32, ppq: u32, params: &TrackParams) -> u32 {
         let mut pulse_no = pulse_no as i32 - params.delay;
 
         // Get the pulses per 16th note.
@@ -69,87 +344,81 @@ impl Track {
         // Make sure pulse no is always positive.
         let pulse_no = pulse_no.max(0) as u32;
 
-        let mut shift = params.shift;
-        while shift < 0 {
-            shift += steps.len() as i32;
+        pulse_no / pp16th
+    }
+
+    /// Returns the pulse number where a step starts, before any nudge is applied.
+    ///
+    /// This is the inverse of `step_count()`.
+    fn step_start(step_count: u32, ppq: u32, params: &TrackParams) -> i64 {
+        let pp16th = ppq / 4;
+
+        let offset = if params.triplet {
+            match step_count % 4 {
+                1 => ppq as i64 / 12,
+                2 => ppq as i64 / 6,
+                _ => 0,
+            }
+        } else if step_count % 2 == 1 {
+            params.swing as i64
+        } else {
+            0
+        };
+
+        step_count as i64 * pp16th as i64 + params.delay as i64 + offset
+    }
+
+    /// Returns a tuple of (loop number, step number) for a step count.
+    ///
+    /// The loop number is counted from the start of the song.
+    fn map_step(step_count: u32, length: u32, params: &TrackParams) -> Option<(u32, u32)> {
+        if length == 0 {
+            return None;
         }
 
+        let shift = params.shift.rem_euclid(length as i32) as u32;
+
         // Do some calculations to determine where we are.
-        let mut play_step = (pulse_no / pp16th + shift as u32) % steps.len() as u32;
+        let step_count = step_count + shift;
+        let loop_no = step_count / length;
+        let mut play_step = step_count % length;
 
         // Apply reverse option.
         if params.reverse {
-            play_step = (steps.len() - 1) as u32 - play_step;
+            play_step = length - 1 - play_step;
         }
 
         if params.triplet && play_step % 4 == 3 {
             // In triplet mode, skip every 4th step.
-            return;
+            return None;
         }
 
-        // Apply re-sort function.
+        // Apply re-sort function, but only if the result is within the active length.
         if let Some(f) = params.resort_fn {
-            play_step = f(play_step);
-        }
-
-        // Check if a previously started note has reached its length.
-        if let Some(scheduled_note_off) = self.scheduled_note_off
-            && self.pulse_count == scheduled_note_off.0
-        {
-            let step_event = StepEvent::NoteOff {
-                pitch: scheduled_note_off.1,
-            };
-            self.event_queue
-                .enqueue(TrackEvent::StepEvent(play_step, step_event))
-                .ok();
-            self.scheduled_note_off = None;
-        }
-
-        if params.enable && (self.play_step.is_none() || play_step != self.play_step.unwrap()) {
-            let mut step = &steps[play_step as usize];
-
-            if params.repeat
-                && !step.enabled()
-                && let Some(last_step) = self.play_step()
-            {
-                step = &steps[last_step as usize];
+            let resorted = f(play_step);
+            if resorted < length {
+                play_step = resorted;
             }
+        }
 
-            self.play_step = Some(play_step);
-
-            // Get the event and emit it.
-            if step.enabled()
-                && let Some(step_event) = step.event().clone()
-            {
-                // If a note is still playing, it must be stopped before triggering a new one.
-                if let Some(scheduled_note_off) = self.scheduled_note_off {
-                    let step_event = StepEvent::NoteOff {
-                        pitch: scheduled_note_off.1,
-                    };
-                    self.event_queue
-                        .enqueue(TrackEvent::StepEvent(play_step, step_event))
-                        .ok();
-                    self.scheduled_note_off = None;
-                }
+        Some((loop_no, play_step))
+    }
 
-                // Enqueue the event.
-                self.event_queue
-                    .enqueue(TrackEvent::StepEvent(play_step, step_event.clone()))
-                    .ok();
-
-                // If the event is a note on, then a corresponding note off
-                // is scheduled for later processing.
-                if let StepEvent::NoteOn { pitch, .. } = step_event {
-                    // Schedule the note off for a 1/32 note length.
-                    let note_off_pulse = self.pulse_count + ppq / 8;
-                    self.scheduled_note_off = Some((note_off_pulse, pitch));
-                }
-            }
-        }
+    /// Resets the playback state, e.g. when the transport starts.
+    ///
+    /// Loop counting for trigger conditions restarts with the next update and
+    /// the random number generator is re-seeded, so playback is reproducible.
+    pub fn reset(&mut self) {
+        self.play_step = None;
+        self.next_step_count = None;
+        self.ratchet_hits.clear();
+        self.loop_origin = None;
+        self.rng.seed(self.seed);
+    }
 
-        // Pulse count is allowed to overflow explicitly.
-        // But it can take a while until that happens.
-        self.pulse_count = self.pulse_count.wrapping_add(1);
+    /// Sets the seed for the random number generator, effective on next reset.
+    pub fn set_seed(&mut self, seed: u32) {
+        self.seed = seed;
     }
 
     /// Returns the next event.
@@ -159,18 +428,30 @@ impl Track {
 
     /// Flushes sustained notes.
     pub fn flush(&mut self) {
-        if let Some(scheduled_note_off) = self.scheduled_note_off {
-            let play_step = self.play_step.unwrap_or_default();
-            let step_event = StepEvent::NoteOff {
-                pitch: scheduled_note_off.1,
-            };
-            self.event_queue
-                .enqueue(TrackEvent::StepEvent(play_step, step_event))
-                .ok();
-            self.scheduled_note_off = None;
+        self.ratchet_hits.clear();
+        self.release_notes();
+    }
+
+    /// Enqueues note offs for all sounding notes, but keeps pending ratchet hits.
+    pub fn release_notes(&mut self) {
+        let play_step = self.play_step.unwrap_or_default();
+        while let Some(pitch) = self.note_offs.next_pending() {
+            self.enqueue_note_off(play_step, pitch);
         }
     }
 
+    /// Enqueues a note off event for a step.
+    fn enqueue_note_off(&mut self, step_no: u32, pitch: Pitch) {
+        let step_event = StepEvent::NoteOff { pitch };
+        self.event_queue
+            .enqueue(TrackEvent::StepEvent(
+                step_no,
+                self.play_step_count,
+                step_event,
+            ))
+            .ok();
+    }
+
     /// Returns the last played step number.
     pub fn play_step(&self) -> Option<u32> {
         self.play_step
@@ -203,12 +484,47 @@ pub struct TrackParams {
 
     /// Triplet mode.
     pub triplet: bool,
+
+    /// Fill mode for trigger conditions.
+    pub fill: bool,
+
+    /// Note length for steps without an individual setting.
+    pub gate: Gate,
 }
 
 /// Events generated by track playback.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum TrackEvent {
-    /// Event for a step.
-    StepEvent(u32, StepEvent),
+    /// Event for a step as (step number, step count, event).
+    ///
+    /// The step count is counted from the start of the song. Note offs carry the step
+    /// count of the last played step.
+    StepEvent(u32, u32, StepEvent),
+
+    /// Event for a hit of a ratcheted step as (step number, step count, event,
+    /// velocity scale in percent).
+    RatchetEvent(u32, u32, StepEvent, u8),
+}
+
+/// Pending hit of a ratcheted step.
+#[derive(Debug, Clone)]
+struct RatchetHit {
+    /// Pulse number when the hit is played.
+    pulse_no: u32,
+
+    /// Number of the step.
+    step_no: u32,
+
+    /// Number of the step, counted from the start of the song.
+    step_count: u32,
+
+    /// Events to play.
+    step_events: StepEvents,
+
+    /// Note length in pulses or `None` for a tied note.
+    note_length: Option<u32>,
+
+    /// Velocity scale in percent.
+    scale: u8,
 }
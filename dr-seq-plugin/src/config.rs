@@ -15,19 +15,52 @@ pub const URL: &str = "https://sourcebox.de";
 /// Email address.
 pub const EMAIL: &str = "info@sourcebox.de";
 
-/// Total number of tracks. Last track is used for global accent.
-pub const TRACKS: usize = 9;
+/// Name of the directory for user data, e.g. presets.
+pub const DATA_DIR: &str = "dr-seq";
 
-/// Labels for the tracks.
-pub const TRACK_LABELS: [&str; TRACKS] =
-    ["BD", "SD", "CHH", "PHH", "OHH", "WD", "MT", "SFX", "ACC"];
+/// Maximum number of note tracks.
+pub const MAX_NOTE_TRACKS: usize = 16;
 
-/// Notes send by the tracks.
-pub const TRACK_NOTES: [u8; TRACKS - 1] = [36, 38, 42, 44, 46, 48, 51, 49];
+/// Minimum number of note tracks.
+pub const MIN_NOTE_TRACKS: usize = 4;
+
+/// Number of note tracks in use by default.
+pub const DEFAULT_NOTE_TRACKS: usize = 8;
+
+/// Number of steps of a pattern.
+pub const STEPS: usize = 16;
+
+/// Number of choke groups the note tracks can be assigned to.
+pub const CHOKE_GROUPS: u32 = 4;
+
+/// Total number of tracks, including the note tracks not in use.
+/// Last track is used for global accent.
+pub const TRACKS: usize = MAX_NOTE_TRACKS + 1;
+
+/// Default labels for the tracks.
+pub const TRACK_LABELS: [&str; TRACKS] = [
+    "BD", "SD", "CHH", "PHH", "OHH", "WD", "MT", "SFX", "LT", "HT", "RS", "CP", "CB", "CY", "RB",
+    "TB", "ACC",
+];
+
+/// Default notes sent by the tracks, following General MIDI.
+pub const TRACK_NOTES: [u8; TRACKS - 1] = [
+    36, 38, 42, 44, 46, 48, 51, 49, 45, 50, 37, 39, 56, 57, 53, 54,
+];
 
 /// Number of the accent track.
 pub const ACCENT_TRACK: u32 = (TRACKS - 1) as u32;
 
+/// Order of the tracks for step repeats of the FLAME mangler.
+/// Tracks not listed follow in their order.
+pub const REPEAT_PRIORITY: [usize; 8] = [1, 2, 3, 4, 5, 6, 0, 7];
+
+/// Number of patterns in the bank.
+pub const BANK_SIZE: usize = 16;
+
+/// Number of bars exported for a single pattern.
+pub const EXPORT_PATTERN_BARS: u32 = 4;
+
 /// Clock pulses per quarter note.
 pub const CLOCK_PPQ: u32 = 384;
 
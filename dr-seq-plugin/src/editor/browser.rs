@@ -0,0 +1,352 @@
+//! Browser for the preset library.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+use vizia_plug::vizia::prelude::*;
+
+use super::EditorEvent;
+use super::style::*;
+use crate::AppParams;
+use crate::library::{Library, LibraryError, PresetInfo};
+use crate::params::Pattern;
+
+/// Number of entries shown at once.
+const VISIBLE_ENTRIES: usize = 7;
+
+/// State of the browser shared by its views.
+struct Browser {
+    /// Library with the entries.
+    library: Library,
+
+    /// Pattern from before the first audition, restored on revert.
+    backup: Option<Pattern>,
+}
+
+/// Signals for the state shown by the browser.
+#[derive(Clone, Copy)]
+struct BrowserSignals {
+    /// Counter incremented when the entries of the library are changed.
+    version: SyncSignal<u32>,
+
+    /// Index of the first entry shown.
+    offset: SyncSignal<usize>,
+
+    /// Index of the selected entry.
+    selected: SyncSignal<Option<usize>>,
+
+    /// Flag if a preset is auditioned.
+    auditioning: SyncSignal<bool>,
+
+    /// Name for saving or renaming a preset.
+    name: SyncSignal<String>,
+
+    /// Category for saving or renaming a preset.
+    category: SyncSignal<String>,
+
+    /// Comma-separated tags for saving or renaming a preset.
+    tags: SyncSignal<String>,
+
+    /// Result of the last operation.
+    status: SyncSignal<String>,
+}
+
+impl BrowserSignals {
+    /// Returns the information entered for saving or renaming a preset.
+    fn info(&self) -> PresetInfo {
+        PresetInfo {
+            name: self.name.get().trim().to_string(),
+            category: self.category.get().trim().to_string(),
+            tags: PresetInfo::parse_tags(&self.tags.get()),
+        }
+    }
+
+    /// Selects an entry and shows its information for editing.
+    fn select(&self, browser: &Browser, index: usize) {
+        let info = browser
+            .library
+            .entries()
+            .get(index)
+            .map(|entry| entry.info.clone())
+            .unwrap_or_default();
+
+        self.selected.update(|s| *s = Some(index));
+        self.name.update(|n| *n = info.name.clone());
+        self.category.update(|c| *c = info.category.clone());
+        self.tags.update(|t| *t = info.tags_text());
+
+        // Keep the selected entry visible.
+        let offset = self.offset.get();
+        if index < offset || index >= offset + VISIBLE_ENTRIES {
+            self.offset
+                .update(|o| *o = index.saturating_sub(VISIBLE_ENTRIES / 2));
+        }
+    }
+
+    /// Shows the result of an operation that changed the entries.
+    fn changed(&self, result: Result<String, LibraryError>) {
+        self.version.update(|v| *v = v.wrapping_add(1));
+        self.show(result);
+    }
+
+    /// Shows the result of an operation.
+    fn show(&self, result: Result<String, LibraryError>) {
+        let text = match result {
+            Ok(text) => text,
+            Err(error) => format!("Preset: {error}"),
+        };
+        self.status.update(|s| *s = text);
+    }
+}
+
+/// Creates the browser.
+///
+/// Clicking an entry loads it into the pattern that is playing. The pattern from before
+/// is kept until the audition is ended by keeping the preset or reverting to the pattern.
+/// - `status`: Signal for showing the result of an operation.
+pub fn create(cx: &mut Context, params: Arc<AppParams>, status: SyncSignal<String>) {
+    let browser = Rc::new(RefCell::new(Browser {
+        library: Library::open(),
+        backup: None,
+    }));
+
+    let signals = BrowserSignals {
+        version: SyncSignal::new(0),
+        offset: SyncSignal::new(0),
+        selected: SyncSignal::new(None),
+        auditioning: SyncSignal::new(false),
+        name: SyncSignal::new(String::new()),
+        category: SyncSignal::new(String::new()),
+        tags: SyncSignal::new(String::new()),
+        status,
+    };
+
+    VStack::new(cx, |cx| {
+        HStack::new(cx, |cx| {
+            Label::new(cx, "Presets")
+                .width(Pixels(60.0))
+                .padding_top(Pixels(5.0));
+
+            Button::new(cx, |cx| Label::new(cx, "◀")).on_press(move |_| {
+                signals
+                    .offset
+                    .update(|o| *o = o.saturating_sub(VISIBLE_ENTRIES));
+            });
+            Element::new(cx).width(Pixels(3.0));
+
+            for slot in 0..VISIBLE_ENTRIES {
+                create_entry(cx, params.clone(), browser.clone(), signals, slot);
+                Element::new(cx).width(Pixels(3.0));
+            }
+
+            Button::new(cx, |cx| Label::new(cx, "▶")).on_press({
+                let browser = browser.clone();
+                move |_| {
+                    let count = browser.borrow().library.entries().len();
+                    let offset = signals.offset.get() + VISIBLE_ENTRIES;
+                    if offset < count {
+                        signals.offset.update(|o| *o = offset);
+                    }
+                }
+            });
+            Element::new(cx).width(ELEMENT_SPACER_WIDTH);
+
+            Button::new(cx, |cx| Label::new(cx, "Keep"))
+                .bind(signals.auditioning, move |handle| {
+                    handle.disabled(!signals.auditioning.get());
+                })
+                .on_press({
+                    let browser = browser.clone();
+                    move |_| {
+                        browser.borrow_mut().backup = None;
+                        signals.auditioning.update(|a| *a = false);
+                    }
+                });
+            Element::new(cx).width(Pixels(5.0));
+            Button::new(cx, |cx| Label::new(cx, "Revert"))
+                .bind(signals.auditioning, move |handle| {
+                    handle.disabled(!signals.auditioning.get());
+                })
+                .on_press({
+                    let browser = browser.clone();
+                    let params = params.clone();
+                    move |eh| {
+                        if let Some(backup) = browser.borrow_mut().backup.take() {
+                            params.pattern.copy_from(&backup);
+                            params.pattern_version.fetch_add(1, Ordering::Release);
+
+                            // Send an event back to the engine.
+                            eh.emit(EditorEvent::UpdateEngine);
+                        }
+                        signals.auditioning.update(|a| *a = false);
+                    }
+                });
+        });
+
+        Element::new(cx).height(Pixels(10.0));
+
+        HStack::new(cx, |cx| {
+            Label::new(cx, "Name")
+                .padding_top(Pixels(3.0))
+                .padding_right(Pixels(5.0));
+            create_textbox(cx, signals.name, 130.0);
+            Element::new(cx).width(ELEMENT_SPACER_WIDTH);
+
+            Label::new(cx, "Category")
+                .padding_top(Pixels(3.0))
+                .padding_right(Pixels(5.0));
+            create_textbox(cx, signals.category, 90.0);
+            Element::new(cx).width(ELEMENT_SPACER_WIDTH);
+
+            Label::new(cx, "Tags")
+                .padding_top(Pixels(3.0))
+                .padding_right(Pixels(5.0));
+            create_textbox(cx, signals.tags, 150.0);
+            Element::new(cx).width(ELEMENT_SPACER_WIDTH);
+
+            Button::new(cx, |cx| Label::new(cx, "Save")).on_press({
+                let browser = browser.clone();
+                let params = params.clone();
+                move |_| {
+                    let info = signals.info();
+                    let name = info.name.clone();
+                    let mut browser = browser.borrow_mut();
+                    let result = browser.library.save(info, &params);
+                    if let Ok(index) = result {
+                        signals.select(&browser, index);
+                    }
+                    signals.changed(result.map(|_| format!("Saved preset {name}")));
+                }
+            });
+            Element::new(cx).width(Pixels(5.0));
+
+            Button::new(cx, |cx| Label::new(cx, "Rename"))
+                .bind(signals.selected, {
+                    let browser = browser.clone();
+                    move |handle| {
+                        handle.disabled(is_read_only(&browser.borrow(), signals.selected.get()));
+                    }
+                })
+                .on_press({
+                    let browser = browser.clone();
+                    move |_| {
+                        let Some(index) = signals.selected.get() else {
+                            return;
+                        };
+                        let info = signals.info();
+                        let name = info.name.clone();
+                        let mut browser = browser.borrow_mut();
+                        let result = browser.library.rename(index, info);
+                        if let Ok(index) = result {
+                            signals.select(&browser, index);
+                        }
+                        signals.changed(result.map(|_| format!("Renamed preset to {name}")));
+                    }
+                });
+            Element::new(cx).width(Pixels(5.0));
+
+            Button::new(cx, |cx| Label::new(cx, "Delete"))
+                .bind(signals.selected, {
+                    let browser = browser.clone();
+                    move |handle| {
+                        handle.disabled(is_read_only(&browser.borrow(), signals.selected.get()));
+                    }
+                })
+                .on_press({
+                    let browser = browser.clone();
+                    move |_| {
+                        let Some(index) = signals.selected.get() else {
+                            return;
+                        };
+                        let mut browser = browser.borrow_mut();
+                        let name = signals.name.get();
+                        let result = browser.library.delete(index);
+                        if result.is_ok() {
+                            signals.selected.update(|s| *s = None);
+                        }
+                        signals.changed(result.map(|_| format!("Deleted preset {name}")));
+                    }
+                });
+        });
+    })
+    .id("browser");
+}
+
+/// Creates a single slot for an entry of the library.
+fn create_entry(
+    cx: &mut Context,
+    params: Arc<AppParams>,
+    browser: Rc<RefCell<Browser>>,
+    signals: BrowserSignals,
+    slot: usize,
+) {
+    let update = {
+        let browser = browser.clone();
+        move |handle: Handle<'_, Label>| {
+            let index = signals.offset.get() + slot;
+            let browser = browser.borrow();
+            let entry = browser.library.entries().get(index);
+
+            handle
+                .text(entry.map(|entry| entry.info.name.as_str()).unwrap_or(""))
+                .toggle_class("inactive", entry.is_none())
+                .toggle_class("factory", entry.is_some_and(|entry| entry.is_read_only()))
+                .toggle_class("selected", signals.selected.get() == Some(index));
+        }
+    };
+
+    Label::new(cx, "")
+        .class("preset-entry")
+        .bind(signals.version, update.clone())
+        .bind(signals.offset, update.clone())
+        .bind(signals.selected, update)
+        .on_press(move |eh| {
+            let index = signals.offset.get() + slot;
+            let mut browser = browser.borrow_mut();
+            let Some(entry) = browser.library.entries().get(index) else {
+                return;
+            };
+            let name = entry.info.name.clone();
+
+            // Keep the pattern from before the first audition.
+            if browser.backup.is_none() {
+                let backup = Pattern::default();
+                backup.copy_from(&params.pattern);
+                browser.backup = Some(backup);
+                signals.auditioning.update(|a| *a = true);
+            }
+
+            signals.select(&browser, index);
+            signals.show(
+                browser
+                    .library
+                    .load(index, &params)
+                    .map(|_| format!("Auditioning preset {name}")),
+            );
+
+            // Let the views know that the pattern has changed.
+            params.pattern_version.fetch_add(1, Ordering::Release);
+
+            // Send an event back to the engine.
+            eh.emit(EditorEvent::UpdateEngine);
+        });
+}
+
+/// Creates a text box for editing the information of a preset.
+fn create_textbox(cx: &mut Context, text: SyncSignal<String>, width: f32) {
+    Textbox::new(cx, text)
+        .class("preset-text")
+        .width(Pixels(width))
+        .on_edit(move |_, new_text| {
+            text.update(|t| *t = new_text);
+        });
+}
+
+/// Returns if the selected entry can't be changed.
+fn is_read_only(browser: &Browser, selected: Option<usize>) -> bool {
+    selected
+        .and_then(|index| browser.library.entries().get(index))
+        .is_none_or(|entry| entry.is_read_only())
+}
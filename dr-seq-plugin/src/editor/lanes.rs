@@ -0,0 +1,718 @@
+//! Lanes for drawing values of the steps of the selected track.
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, AtomicU32};
+
+use vizia_plug::vizia::prelude::*;
+
+use dr_seq_engine::{Euclid, Scale};
+
+use super::EditorEvent;
+use super::signals::SharedSignals;
+use super::style::*;
+use crate::AppParams;
+use crate::config::*;
+use crate::params::{MAX_LAYERS, NOTE_NAMES, StepState, TrackMode, euclid_step_state};
+
+/// Lowest note shown in the pitch lane.
+const PITCH_LANE_LOW: u32 = 36;
+
+/// Highest note shown in the pitch lane.
+const PITCH_LANE_HIGH: u32 = 59;
+
+/// Lanes that can be shown for the selected track.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Lane {
+    /// Velocities of the steps.
+    #[default]
+    Velocity,
+
+    /// Notes of the steps.
+    Pitch,
+
+    /// Layered notes of the steps.
+    Layers,
+
+    /// Euclidean rhythm generator.
+    Euclid,
+
+    /// Accent lane of the track.
+    Accent,
+}
+
+impl From<Lane> for u32 {
+    fn from(value: Lane) -> Self {
+        match value {
+            Lane::Velocity => 0,
+            Lane::Pitch => 1,
+            Lane::Layers => 2,
+            Lane::Euclid => 3,
+            Lane::Accent => 4,
+        }
+    }
+}
+
+impl From<u32> for Lane {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => Lane::Pitch,
+            2 => Lane::Layers,
+            3 => Lane::Euclid,
+            4 => Lane::Accent,
+            _ => Lane::Velocity,
+        }
+    }
+}
+
+/// Creates the lanes.
+pub fn create(cx: &mut Context, params: Arc<AppParams>, signals: &SharedSignals) {
+    let selected_track = signals.selected_track;
+    let selected_lane = signals.selected_lane;
+
+    VStack::new(cx, |cx| {
+        // Lanes are created for each note track, but only the ones for
+        // the selected track are shown.
+        for track in 0..TRACKS - 1 {
+            HStack::new(cx, |cx| {
+                VStack::new(cx, |cx| {
+                    create_lane_label(cx, selected_lane, Lane::Velocity, "VEL");
+                    create_lane_label(cx, selected_lane, Lane::Pitch, "PITCH");
+                    create_lane_label(cx, selected_lane, Lane::Layers, "LAYER");
+                    create_lane_label(cx, selected_lane, Lane::Euclid, "EUCL");
+                    create_lane_label(cx, selected_lane, Lane::Accent, "ACC");
+                    create_mode_label(cx, signals.modes[track]);
+                })
+                .width(Pixels(45.0));
+
+                create_lane(cx, selected_lane, Lane::Velocity, |cx| {
+                    create_velocity_lane(cx, params.clone(), signals, track);
+                });
+                create_lane(cx, selected_lane, Lane::Pitch, |cx| {
+                    create_pitch_lane(cx, params.clone(), signals, track);
+                });
+                create_lane(cx, selected_lane, Lane::Layers, |cx| {
+                    create_layer_lane(cx, signals, track);
+                });
+                create_lane(cx, selected_lane, Lane::Euclid, |cx| {
+                    create_euclid_lane(cx, signals, track);
+                });
+                create_lane(cx, selected_lane, Lane::Accent, |cx| {
+                    create_accent_lane(cx, signals, track);
+                });
+            })
+            .bind(selected_track, move |handle| {
+                let selected = selected_track.get().load(Ordering::Relaxed) as usize;
+                handle.display(if selected == track {
+                    Display::Flex
+                } else {
+                    Display::None
+                });
+            });
+        }
+    })
+    .id("lanes");
+}
+
+/// Creates a container for a lane, which is only shown while the lane is selected.
+fn create_lane(
+    cx: &mut Context,
+    selected_lane: SyncSignal<Arc<AtomicU32>>,
+    lane: Lane,
+    content: impl FnOnce(&mut Context),
+) {
+    HStack::new(cx, content).bind(selected_lane, move |handle| {
+        let selected = Lane::from(selected_lane.get().load(Ordering::Relaxed));
+        handle.display(if selected == lane {
+            Display::Flex
+        } else {
+            Display::None
+        });
+    });
+}
+
+/// Creates a label selecting a lane when clicked.
+fn create_lane_label(
+    cx: &mut Context,
+    selected_lane: SyncSignal<Arc<AtomicU32>>,
+    lane: Lane,
+    text: &str,
+) {
+    Label::new(cx, text)
+        .class("lane-label")
+        .bind(selected_lane, move |handle| {
+            let selected = Lane::from(selected_lane.get().load(Ordering::Relaxed));
+            handle.toggle_class("selected", selected == lane);
+        })
+        .on_press(move |_| {
+            selected_lane.update(|l| l.store(lane.into(), Ordering::Relaxed));
+        });
+}
+
+/// Creates a label toggling the melodic mode of a track when clicked.
+fn create_mode_label(cx: &mut Context, mode: SyncSignal<Arc<AtomicU32>>) {
+    Label::new(cx, "MEL")
+        .class("lane-label")
+        .bind(mode, move |handle| {
+            let value = TrackMode::from(mode.get().load(Ordering::Relaxed));
+            handle.toggle_class("selected", value == TrackMode::Melodic);
+        })
+        .on_press(move |eh| {
+            mode.update(|m| {
+                let value = match TrackMode::from(m.load(Ordering::Relaxed)) {
+                    TrackMode::Drum => TrackMode::Melodic,
+                    TrackMode::Melodic => TrackMode::Drum,
+                };
+                m.store(value.into(), Ordering::Relaxed);
+            });
+
+            // Send an event back to the engine.
+            eh.emit(EditorEvent::UpdateEngine);
+        });
+}
+
+/// Creates the velocity lane of a track.
+fn create_velocity_lane(
+    cx: &mut Context,
+    params: Arc<AppParams>,
+    signals: &SharedSignals,
+    track: usize,
+) {
+    for step in 0..16 {
+        let state = signals.steps[track][step];
+        let velocity = signals.velocities[track][step];
+        create_velocity_bar(cx, params.clone(), state, velocity);
+        Element::new(cx).width(Pixels(3.0));
+
+        if step % 4 == 3 && step != 15 {
+            // Add additional space after block of 4 bars.
+            Element::new(cx).width(GRID_COL_SPACER_WIDTH);
+        }
+    }
+}
+
+/// Creates a single bar showing the velocity of a step.
+///
+/// Pressing the mouse button on an enabled step sets an individual velocity
+/// from the vertical position. Moving the mouse with the button pressed draws
+/// the velocities of multiple steps.
+fn create_velocity_bar(
+    cx: &mut Context,
+    params: Arc<AppParams>,
+    state: SyncSignal<Arc<AtomicU32>>,
+    velocity: SyncSignal<Arc<AtomicU32>>,
+) {
+    VStack::new(cx, |cx| {
+        Element::new(cx)
+            .class("value")
+            .bind(state, {
+                let params = params.clone();
+                move |handle| {
+                    let value = step_velocity(&params, state, velocity);
+                    handle.height(Percentage(value.unwrap_or(0) as f32 / 127.0 * 100.0));
+                }
+            })
+            .bind(velocity, move |handle| {
+                let value = step_velocity(&params, state, velocity);
+                handle.height(Percentage(value.unwrap_or(0) as f32 / 127.0 * 100.0));
+            });
+    })
+    .class("lane-bar")
+    .on_mouse_down(move |eh, button| {
+        if button == MouseButton::Left {
+            let y = eh.mouse().cursor_y;
+            draw_velocity(eh, state, velocity, y);
+        }
+    })
+    .on_mouse_move(move |eh, _, y| {
+        if eh.mouse().left.state == MouseButtonState::Pressed {
+            draw_velocity(eh, state, velocity, y);
+        }
+    });
+}
+
+/// Returns the velocity of a step in range 0-127 or `None` if the step is disabled.
+fn step_velocity(
+    params: &AppParams,
+    state: SyncSignal<Arc<AtomicU32>>,
+    velocity: SyncSignal<Arc<AtomicU32>>,
+) -> Option<u32> {
+    let step_state = StepState::from(state.get().load(Ordering::Relaxed));
+
+    match step_state {
+        StepState::Off => None,
+        StepState::Normal => Some(params.normal_velocity.value() as u32),
+        StepState::Accent => {
+            if params.accent_vel_mode.value() {
+                Some(params.accent_velocity.value() as u32)
+            } else {
+                Some(
+                    (params.normal_velocity.value() + params.accent_velocity.value()).min(127)
+                        as u32,
+                )
+            }
+        }
+        StepState::Weak => Some(params.weak_velocity.value() as u32),
+        StepState::Ghost => Some(params.ghost_velocity.value() as u32),
+        StepState::Custom => Some(velocity.get().load(Ordering::Relaxed).min(127)),
+    }
+}
+
+/// Sets the individual velocity of an enabled step from a vertical mouse position.
+fn draw_velocity(
+    eh: &mut EventContext,
+    state: SyncSignal<Arc<AtomicU32>>,
+    velocity: SyncSignal<Arc<AtomicU32>>,
+    y: f32,
+) {
+    let step_state = StepState::from(state.get().load(Ordering::Relaxed));
+
+    if step_state == StepState::Off {
+        return;
+    }
+
+    let bounds = eh.bounds();
+    let value = ((bounds.bottom() - y) / bounds.height()).clamp(0.0, 1.0) * 127.0;
+
+    velocity.update(|v| v.store(value.round() as u32, Ordering::Relaxed));
+    state.update(|s| s.store(StepState::Custom.into(), Ordering::Relaxed));
+
+    // Send an event back to the engine.
+    eh.emit(EditorEvent::UpdateEngine);
+}
+
+/// Creates the pitch lane of a track.
+///
+/// The lane is drawn like a piano roll, with a keyboard at the right side
+/// to keep the bars aligned with the cells of the grid.
+fn create_pitch_lane(
+    cx: &mut Context,
+    params: Arc<AppParams>,
+    signals: &SharedSignals,
+    track: usize,
+) {
+    for step in 0..16 {
+        let note = signals.notes[track][step];
+        create_pitch_bar(cx, params.clone(), note);
+        Element::new(cx).width(Pixels(3.0));
+
+        if step % 4 == 3 && step != 15 {
+            // Add additional space after block of 4 bars.
+            Element::new(cx).width(GRID_COL_SPACER_WIDTH);
+        }
+    }
+
+    VStack::new(cx, |cx| {
+        for note in (PITCH_LANE_LOW..=PITCH_LANE_HIGH).rev() {
+            Element::new(cx)
+                .class("key")
+                .toggle_class("black", NOTE_NAMES[note as usize % 12].ends_with('#'));
+        }
+    })
+    .class("keyboard");
+}
+
+/// Creates a single bar showing the note of a step.
+///
+/// Pressing the mouse button sets the note from the vertical position. Moving the mouse
+/// with the button pressed draws the notes of multiple steps. The marker shows the note
+/// after quantization to the selected scale.
+fn create_pitch_bar(cx: &mut Context, params: Arc<AppParams>, note: SyncSignal<Arc<AtomicU32>>) {
+    let range = (PITCH_LANE_HIGH - PITCH_LANE_LOW + 1) as f32;
+
+    VStack::new(cx, |cx| {
+        VStack::new(cx, |cx| {
+            Element::new(cx)
+                .class("marker")
+                .height(Percentage(100.0 / range))
+                .bind(note, {
+                    let params = params.clone();
+                    move |handle| {
+                        let value = step_note(&params, note).clamp(PITCH_LANE_LOW, PITCH_LANE_HIGH);
+                        handle.top(Percentage((PITCH_LANE_HIGH - value) as f32 / range * 100.0));
+                    }
+                });
+        })
+        .class("lane-bar")
+        .on_mouse_down(move |eh, button| {
+            if button == MouseButton::Left {
+                let y = eh.mouse().cursor_y;
+                draw_note(eh, note, y);
+            }
+        })
+        .on_mouse_move(move |eh, _, y| {
+            if eh.mouse().left.state == MouseButtonState::Pressed {
+                draw_note(eh, note, y);
+            }
+        });
+
+        Label::new(cx, "")
+            .class("note-name")
+            .bind(note, move |handle| {
+                handle.text(note_name(step_note(&params, note)));
+            });
+    });
+}
+
+/// Returns the note of a step after quantization to the selected scale.
+fn step_note(params: &AppParams, note: SyncSignal<Arc<AtomicU32>>) -> u32 {
+    let scale = Scale::ALL
+        .get(params.scale.value() as usize)
+        .copied()
+        .unwrap_or_default();
+    let note = note.get().load(Ordering::Relaxed).min(127) as i32;
+
+    scale.quantize(note, params.root.value()).clamp(0, 127) as u32
+}
+
+/// Returns the name of a note including the octave, e.g. `C1` for note 36.
+fn note_name(note: u32) -> String {
+    format!("{}{}", NOTE_NAMES[note as usize % 12], note as i32 / 12 - 2)
+}
+
+/// Sets the note of a step from a vertical mouse position.
+fn draw_note(eh: &mut EventContext, note: SyncSignal<Arc<AtomicU32>>, y: f32) {
+    let bounds = eh.bounds();
+    let range = (PITCH_LANE_HIGH - PITCH_LANE_LOW + 1) as f32;
+    let position = ((bounds.bottom() - y) / bounds.height()).clamp(0.0, 1.0);
+    let value = (PITCH_LANE_LOW + (position * range) as u32).min(PITCH_LANE_HIGH);
+
+    note.update(|n| n.store(value, Ordering::Relaxed));
+
+    // Send an event back to the engine.
+    eh.emit(EditorEvent::UpdateEngine);
+}
+
+/// Creates the layer lane of a track.
+///
+/// Each row toggles a layered note on the steps. The notes of the rows are shown
+/// at the right side, a left click raises the note by a semitone, a right click
+/// lowers it. With the shift key pressed, the note is changed by an octave.
+fn create_layer_lane(cx: &mut Context, signals: &SharedSignals, track: usize) {
+    for step in 0..16 {
+        let layers = signals.layers[track][step];
+
+        VStack::new(cx, |cx| {
+            for layer in 0..MAX_LAYERS {
+                create_layer_cell(cx, layers, layer);
+            }
+        })
+        .class("lane-bar");
+        Element::new(cx).width(Pixels(3.0));
+
+        if step % 4 == 3 && step != 15 {
+            // Add additional space after block of 4 bars.
+            Element::new(cx).width(GRID_COL_SPACER_WIDTH);
+        }
+    }
+
+    VStack::new(cx, |cx| {
+        for layer in 0..MAX_LAYERS {
+            create_layer_note(cx, signals.layer_notes[track][layer]);
+        }
+    })
+    .class("layer-notes");
+}
+
+/// Creates a cell toggling a layered note of a step.
+fn create_layer_cell(cx: &mut Context, layers: SyncSignal<Arc<AtomicU32>>, layer: usize) {
+    Element::new(cx)
+        .class("layer")
+        .bind(layers, move |handle| {
+            let value = layers.get().load(Ordering::Relaxed);
+            handle.toggle_class("active", value & (1 << layer) != 0);
+        })
+        .on_press(move |eh| {
+            layers.update(|l| {
+                l.fetch_xor(1 << layer, Ordering::Relaxed);
+            });
+
+            // Send an event back to the engine.
+            eh.emit(EditorEvent::UpdateEngine);
+        });
+}
+
+/// Creates a label showing a layer note, which can be changed with the mouse buttons.
+fn create_layer_note(cx: &mut Context, note: SyncSignal<Arc<AtomicU32>>) {
+    Label::new(cx, "")
+        .class("layer-note")
+        .bind(note, move |handle| {
+            handle.text(note_name(note.get().load(Ordering::Relaxed).min(127)));
+        })
+        .on_mouse_down(move |eh, button| {
+            let interval = if eh.modifiers().contains(Modifiers::SHIFT) {
+                12
+            } else {
+                1
+            };
+
+            let value = note.get().load(Ordering::Relaxed).min(127) as i32;
+            let value = match button {
+                MouseButton::Left => value + interval,
+                MouseButton::Right => value - interval,
+                _ => return,
+            };
+
+            note.update(|n| n.store(value.clamp(0, 127) as u32, Ordering::Relaxed));
+
+            // Send an event back to the engine.
+            eh.emit(EditorEvent::UpdateEngine);
+        });
+}
+
+/// Signals for the euclidean rhythm of a track.
+#[derive(Clone, Copy)]
+struct EuclidSignals {
+    /// Flag if the rhythm is played instead of the steps.
+    enabled: SyncSignal<Arc<AtomicBool>>,
+
+    /// Number of hits.
+    pulses: SyncSignal<Arc<AtomicU32>>,
+
+    /// Length in steps.
+    steps: SyncSignal<Arc<AtomicU32>>,
+
+    /// Rotation in steps.
+    rotation: SyncSignal<Arc<AtomicU32>>,
+
+    /// States of the steps of the track.
+    states: [SyncSignal<Arc<AtomicU32>>; 16],
+
+    /// Active length of the track.
+    length: SyncSignal<Arc<AtomicU32>>,
+}
+
+impl EuclidSignals {
+    /// Returns the hits of the rhythm.
+    fn hits(&self) -> [bool; 16] {
+        Euclid::new(
+            self.pulses.get().load(Ordering::Relaxed),
+            self.steps.get().load(Ordering::Relaxed),
+            self.rotation.get().load(Ordering::Relaxed) as i32,
+        )
+        .hits::<16>()
+    }
+
+    /// Writes the rhythm into the steps and disables it.
+    ///
+    /// Steps with a hit keep their state if they are already enabled.
+    fn commit(&self) {
+        for (state, hit) in self.states.iter().zip(self.hits()) {
+            let step_state = StepState::from(state.get().load(Ordering::Relaxed));
+            let new_state = euclid_step_state(step_state, hit);
+            state.update(|s| s.store(new_state.into(), Ordering::Relaxed));
+        }
+
+        let steps = self.steps.get().load(Ordering::Relaxed).clamp(1, 16);
+        self.length.update(|l| l.store(steps, Ordering::Relaxed));
+        self.enabled.update(|e| e.store(false, Ordering::Relaxed));
+    }
+}
+
+/// Creates the euclidean rhythm lane of a track.
+///
+/// The lane shows a preview of the rhythm, which is played instead of the steps while
+/// enabled. Committing writes the rhythm into the steps. The values for hits, length and
+/// rotation are raised with a left click and lowered with a right click.
+fn create_euclid_lane(cx: &mut Context, signals: &SharedSignals, track: usize) {
+    let signals = EuclidSignals {
+        enabled: signals.euclid_enabled[track],
+        pulses: signals.euclid_pulses[track],
+        steps: signals.euclid_steps[track],
+        rotation: signals.euclid_rotations[track],
+        states: signals.steps[track],
+        length: signals.lengths[track],
+    };
+
+    for step in 0..16 {
+        create_euclid_bar(cx, signals, step);
+        Element::new(cx).width(Pixels(3.0));
+
+        if step % 4 == 3 && step != 15 {
+            // Add additional space after block of 4 bars.
+            Element::new(cx).width(GRID_COL_SPACER_WIDTH);
+        }
+    }
+
+    VStack::new(cx, |cx| {
+        HStack::new(cx, |cx| {
+            create_euclid_value(cx, signals.pulses, "HITS", 0);
+            create_euclid_value(cx, signals.steps, "LEN", 1);
+            create_euclid_value(cx, signals.rotation, "ROT", 0);
+        });
+
+        HStack::new(cx, |cx| {
+            let enabled = signals.enabled;
+
+            Label::new(cx, "PLAY")
+                .class("lane-label")
+                .bind(enabled, move |handle| {
+                    handle.toggle_class("selected", enabled.get().load(Ordering::Relaxed));
+                })
+                .on_press(move |eh| {
+                    enabled.update(|e| {
+                        e.fetch_xor(true, Ordering::Relaxed);
+                    });
+
+                    // Send an event back to the engine.
+                    eh.emit(EditorEvent::UpdateEngine);
+                });
+
+            Label::new(cx, "COMMIT")
+                .class("lane-label")
+                .on_press(move |eh| {
+                    signals.commit();
+
+                    // Send an event back to the engine.
+                    eh.emit(EditorEvent::UpdateEngine);
+                });
+        });
+    })
+    .class("euclid-controls");
+}
+
+/// Creates a single bar showing if a step is a hit of the euclidean rhythm.
+fn create_euclid_bar(cx: &mut Context, signals: EuclidSignals, step: usize) {
+    let update = move |handle: Handle<'_, Element>| {
+        let steps = signals.steps.get().load(Ordering::Relaxed) as usize;
+        handle
+            .toggle_class("hit", signals.hits()[step])
+            .toggle_class("inactive", step >= steps)
+            .toggle_class("preview", !signals.enabled.get().load(Ordering::Relaxed));
+    };
+
+    Element::new(cx)
+        .class("lane-bar")
+        .class("euclid")
+        .bind(signals.enabled, update)
+        .bind(signals.pulses, update)
+        .bind(signals.steps, update)
+        .bind(signals.rotation, update);
+}
+
+/// Creates a label showing a value of the euclidean rhythm, which can be changed
+/// with the mouse buttons.
+/// - `min`: Minimum value, the maximum is the capacity of the pattern.
+fn create_euclid_value(
+    cx: &mut Context,
+    value: SyncSignal<Arc<AtomicU32>>,
+    text: &'static str,
+    min: u32,
+) {
+    VStack::new(cx, |cx| {
+        Label::new(cx, text).class("euclid-name");
+        Label::new(cx, "")
+            .class("euclid-value")
+            .bind(value, move |handle| {
+                handle.text(value.get().load(Ordering::Relaxed).to_string());
+            });
+    })
+    .class("euclid-setting")
+    .on_mouse_down(move |eh, button| {
+        let current = value.get().load(Ordering::Relaxed);
+        let new_value = match button {
+            MouseButton::Left => current.saturating_add(1),
+            MouseButton::Right => current.saturating_sub(1),
+            _ => return,
+        };
+
+        value.update(|v| v.store(new_value.clamp(min, 16), Ordering::Relaxed));
+
+        // Send an event back to the engine.
+        eh.emit(EditorEvent::UpdateEngine);
+    });
+}
+
+/// Creates the accent lane of a track.
+///
+/// Clicking a bar toggles the accent of the step. The lane is played with its own
+/// accent velocity in addition to the accent track while it is switched on.
+fn create_accent_lane(cx: &mut Context, signals: &SharedSignals, track: usize) {
+    let length = signals.lengths[track];
+
+    for step in 0..16 {
+        create_accent_bar(cx, signals.accents[track][step], length, step);
+        Element::new(cx).width(Pixels(3.0));
+
+        if step % 4 == 3 && step != 15 {
+            // Add additional space after block of 4 bars.
+            Element::new(cx).width(GRID_COL_SPACER_WIDTH);
+        }
+    }
+
+    let playing = signals.accent_lanes[track];
+    let velocity = signals.accent_velocities[track];
+
+    VStack::new(cx, |cx| {
+        VStack::new(cx, |cx| {
+            Label::new(cx, "VEL").class("accent-name");
+            Label::new(cx, "")
+                .class("accent-value")
+                .bind(velocity, move |handle| {
+                    handle.text(velocity.get().load(Ordering::Relaxed).min(127).to_string());
+                });
+        })
+        .class("accent-setting")
+        .on_mouse_down(move |eh, button| {
+            // The shift key changes the velocity in larger steps.
+            let amount = if eh.modifiers().contains(Modifiers::SHIFT) {
+                10
+            } else {
+                1
+            };
+            let current = velocity.get().load(Ordering::Relaxed).min(127);
+            let new_value = match button {
+                MouseButton::Left => (current + amount).min(127),
+                MouseButton::Right => current.saturating_sub(amount),
+                _ => return,
+            };
+
+            velocity.update(|v| v.store(new_value, Ordering::Relaxed));
+
+            // Send an event back to the engine.
+            eh.emit(EditorEvent::UpdateEngine);
+        });
+
+        Label::new(cx, "PLAY")
+            .class("lane-label")
+            .bind(playing, move |handle| {
+                handle.toggle_class("selected", playing.get().load(Ordering::Relaxed));
+            })
+            .on_press(move |eh| {
+                playing.update(|p| {
+                    p.fetch_xor(true, Ordering::Relaxed);
+                });
+
+                // Send an event back to the engine.
+                eh.emit(EditorEvent::UpdateEngine);
+            });
+    })
+    .class("accent-controls");
+}
+
+/// Creates a single bar showing if a step is set on the accent lane.
+fn create_accent_bar(
+    cx: &mut Context,
+    accent: SyncSignal<Arc<AtomicBool>>,
+    length: SyncSignal<Arc<AtomicU32>>,
+    step: usize,
+) {
+    Element::new(cx)
+        .class("lane-bar")
+        .class("accent")
+        .bind(accent, move |handle| {
+            handle.toggle_class("set", accent.get().load(Ordering::Relaxed));
+        })
+        .bind(length, move |handle| {
+            let length = length.get().load(Ordering::Relaxed) as usize;
+            handle.toggle_class("inactive", step >= length);
+        })
+        .on_press(move |eh| {
+            accent.update(|a| {
+                a.fetch_xor(true, Ordering::Relaxed);
+            });
+
+            // Send an event back to the engine.
+            eh.emit(EditorEvent::UpdateEngine);
+        });
+}
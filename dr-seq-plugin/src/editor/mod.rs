@@ -1,11 +1,19 @@
 //! Editor module using vizia.
 
+mod browser;
 mod controls;
+mod lanes;
+mod signals;
+mod song;
 mod style;
 mod tracks;
 
+use std::cell::Cell;
+use std::rc::Rc;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::SyncSender;
+use std::time::Duration;
 
 use nice_plug::prelude::Editor;
 use vizia_plug::vizia::prelude::*;
@@ -14,16 +22,18 @@ use vizia_plug::{ViziaState, ViziaTheming, create_vizia_editor};
 
 use crate::AppParams;
 use crate::config::NAME;
+use crate::midi_file::{self, ExportSource, SmfFormat, VelocityMapping};
 use controls::*;
+use signals::SharedSignals;
+
+/// Interval for checking if the pattern was changed outside of the editor.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(50);
 
 /// Events shared with the audio thread.
 #[derive(Debug, Clone)]
 pub enum EditorEvent {
     /// Update the engine.
     UpdateEngine,
-
-    /// Load a preset.
-    LoadPreset(u32),
 }
 
 /// Event channels for message exchange.
@@ -42,7 +52,7 @@ impl Model for EventChannels {
 
 /// Returns the default state.
 pub fn default_state() -> Arc<ViziaState> {
-    ViziaState::new(|| (800, 500))
+    ViziaState::new(|| (945, 770))
 }
 
 /// Create the editor.
@@ -61,18 +71,52 @@ pub fn create(
 
         ResizeHandle::new(cx);
 
+        let signals = Rc::new(SharedSignals::new(&params));
+
+        // The pattern can be changed by the audio thread, e.g. on a pattern switch,
+        // so the views are refreshed when this is detected.
+        let pattern_version = params.pattern_version.clone();
+        let shown_version = Cell::new(pattern_version.load(Ordering::Acquire));
+        let refresh_timer = cx.add_timer(REFRESH_INTERVAL, None, {
+            let signals = signals.clone();
+            move |_, action| {
+                if let TimerAction::Tick(_) = action {
+                    let version = pattern_version.load(Ordering::Acquire);
+                    if version != shown_version.replace(version) {
+                        signals.refresh();
+                    }
+                }
+            }
+        });
+        cx.start_timer(refresh_timer);
+
+        // Result of the last file operation.
+        let status = SyncSignal::new(String::new());
+
         HStack::new(cx, |cx| {
             Label::new(cx, NAME);
+            Label::new(cx, "")
+                .class("status")
+                .bind(status, move |handle| {
+                    handle.text(status.get());
+                });
         })
         .id("header");
 
         Grid::new(
             cx,
-            vec![Pixels(640.0), Pixels(120.0)],
-            vec![Pixels(310.0), Pixels(50.0)],
+            vec![Pixels(785.0), Pixels(120.0)],
+            vec![
+                Pixels(310.0),
+                Pixels(100.0),
+                Pixels(50.0),
+                Pixels(50.0),
+                Pixels(50.0),
+                Pixels(90.0),
+            ],
             |cx| {
                 VStack::new(cx, |cx| {
-                    tracks::create(cx, params.clone());
+                    tracks::create(cx, params.clone(), &signals);
                 })
                 .row_start(0)
                 .column_start(0);
@@ -102,6 +146,22 @@ pub fn create(
                 .row_start(0)
                 .column_start(1);
 
+                VStack::new(cx, |cx| {
+                    lanes::create(cx, params.clone(), &signals);
+                })
+                .row_start(1)
+                .column_start(0);
+
+                VStack::new(cx, |cx| {
+                    Label::new(cx, "Scale");
+                    param_slider(cx, &params.scale);
+                    Element::new(cx).height(Pixels(10.0));
+                    Label::new(cx, "Root");
+                    param_slider(cx, &params.root);
+                })
+                .row_start(1)
+                .column_start(1);
+
                 VStack::new(cx, |cx| {
                     HStack::new(cx, |cx| {
                         Label::new(cx, "Swing")
@@ -110,26 +170,40 @@ pub fn create(
                         ParamSlider::new(cx, &params.swing).class("slider");
                         Element::new(cx).width(Pixels(20.0));
                         ParamButton::new(cx, &params.triplet);
+                        Element::new(cx).width(Pixels(5.0));
+                        ParamButton::new(cx, &params.fill);
                         Element::new(cx).width(Pixels(20.0));
 
-                        Label::new(cx, "Presets")
+                        Label::new(cx, "Kit")
                             .padding_top(Pixels(5.0))
                             .padding_right(Pixels(10.0));
-                        ButtonGroup::new(cx, |cx| {
-                            for n in 0..8 {
-                                Button::new(cx, |cx| Label::new(cx, format!("{}", n + 1)))
-                                    .on_press(move |cx| {
-                                        cx.emit(EditorEvent::LoadPreset(n));
-                                    });
-                                Element::new(cx).width(Pixels(5.0));
-                            }
-                        });
+                        tracks::create_kit_selector(cx, &signals);
+                        Element::new(cx).width(Pixels(20.0));
+
+                        Label::new(cx, "Tracks")
+                            .padding_top(Pixels(5.0))
+                            .padding_right(Pixels(10.0));
+                        tracks::create_track_setup(cx, params.clone(), &signals);
                     });
                 })
-                .row_start(1)
+                .row_start(2)
                 .column_start(0)
                 .padding_top(Pixels(10.0));
 
+                VStack::new(cx, |cx| {
+                    Label::new(cx, "Pattern");
+                    HStack::new(cx, |cx| {
+                        param_slider(cx, &params.pattern_select);
+                        Element::new(cx).width(Pixels(3.0));
+                        ParamButton::new(cx, &params.switch_mode)
+                            .height(Pixels(20.0))
+                            .top(Pixels(3.0))
+                            .with_label("loop");
+                    });
+                })
+                .row_start(2)
+                .column_start(1);
+
                 VStack::new(cx, |cx| {
                     HStack::new(cx, |cx| {
                         Label::new(cx, "Manglers")
@@ -148,13 +222,156 @@ pub fn create(
                         ParamButton::new(cx, &params.mangler_fast);
                         Element::new(cx).width(Pixels(5.0));
                         ParamButton::new(cx, &params.mangler_slow);
+                        Element::new(cx).width(Pixels(20.0));
+
+                        Label::new(cx, "Choke")
+                            .padding_top(Pixels(5.0))
+                            .padding_right(Pixels(10.0));
+                        tracks::create_choke_mode(cx, &signals);
                     });
                 })
-                .row_start(2)
+                .row_start(3)
+                .column_start(0)
+                .padding_top(Pixels(10.0));
+
+                VStack::new(cx, |cx| {
+                    let multi_track = SyncSignal::new(Arc::new(AtomicBool::new(true)));
+                    HStack::new(cx, |cx| {
+                        Button::new(cx, |cx| Label::new(cx, "Export MIDI…")).on_press({
+                            let params = params.clone();
+                            move |_| {
+                                let format = if multi_track.get().load(Ordering::Relaxed) {
+                                    SmfFormat::MultiTrack
+                                } else {
+                                    SmfFormat::SingleTrack
+                                };
+                                if let Some(text) = export_midi(&params, format) {
+                                    status.update(|status| *status = text);
+                                }
+                            }
+                        });
+                        Element::new(cx).width(Pixels(3.0));
+                        Label::new(cx, "")
+                            .class("lane-label")
+                            .bind(multi_track, move |handle| {
+                                let multi_track = multi_track.get().load(Ordering::Relaxed);
+                                handle.text(if multi_track { "T1" } else { "T0" });
+                            })
+                            .on_press(move |_| {
+                                multi_track.update(|m| {
+                                    m.fetch_xor(true, Ordering::Relaxed);
+                                });
+                            });
+                    });
+                })
+                .row_start(3)
+                .column_start(1)
+                .padding_top(Pixels(10.0));
+
+                VStack::new(cx, |cx| {
+                    song::create(cx, params.clone());
+                })
+                .row_start(4)
+                .column_start(0)
+                .padding_top(Pixels(10.0));
+
+                VStack::new(cx, |cx| {
+                    let exact = SyncSignal::new(Arc::new(AtomicBool::new(false)));
+                    HStack::new(cx, |cx| {
+                        Button::new(cx, |cx| Label::new(cx, "Import MIDI…")).on_press({
+                            let params = params.clone();
+                            move |eh| {
+                                let velocities = if exact.get().load(Ordering::Relaxed) {
+                                    VelocityMapping::Exact
+                                } else {
+                                    VelocityMapping::States
+                                };
+                                if let Some(text) = import_midi(&params, velocities) {
+                                    status.update(|status| *status = text);
+
+                                    // Send an event back to the engine.
+                                    eh.emit(EditorEvent::UpdateEngine);
+                                }
+                            }
+                        });
+                        Element::new(cx).width(Pixels(3.0));
+                        Label::new(cx, "")
+                            .class("lane-label")
+                            .bind(exact, move |handle| {
+                                let exact = exact.get().load(Ordering::Relaxed);
+                                handle.text(if exact { "EX" } else { "ST" });
+                            })
+                            .on_press(move |_| {
+                                exact.update(|e| {
+                                    e.fetch_xor(true, Ordering::Relaxed);
+                                });
+                            });
+                    });
+                })
+                .row_start(4)
+                .column_start(1)
+                .padding_top(Pixels(10.0));
+
+                VStack::new(cx, |cx| {
+                    browser::create(cx, params.clone(), status);
+                })
+                .row_start(5)
                 .column_start(0)
+                .column_span(2)
                 .padding_top(Pixels(10.0));
             },
         )
         .id("main");
     })
 }
+
+/// Asks for a file name and exports the pattern or, in song mode, the chain as MIDI file.
+///
+/// Returns a summary of the export or `None` if no file was selected.
+fn export_midi(params: &AppParams, format: SmfFormat) -> Option<String> {
+    let source = if params.song_mode.value() {
+        ExportSource::Chain
+    } else {
+        ExportSource::Pattern
+    };
+
+    let path = rfd::FileDialog::new()
+        .add_filter("MIDI file", &["mid"])
+        .set_file_name("dr-seq.mid")
+        .save_file()?;
+
+    let data = midi_file::export(params, source, format);
+    match std::fs::write(&path, data) {
+        Ok(()) => Some(format!("Exported to {}", path.display())),
+        Err(error) => Some(format!("Export failed: {error}")),
+    }
+}
+
+/// Asks for a MIDI file and imports it into the pattern that is playing.
+///
+/// Returns a summary of the import or `None` if no file was selected.
+fn import_midi(params: &AppParams, velocities: VelocityMapping) -> Option<String> {
+    let path = rfd::FileDialog::new()
+        .add_filter("MIDI file", &["mid", "midi"])
+        .pick_file()?;
+
+    let result = std::fs::read(&path)
+        .map_err(|error| error.to_string())
+        .and_then(|data| {
+            midi_file::import(&data, params, velocities).map_err(|error| error.to_string())
+        });
+
+    match result {
+        Ok(report) => {
+            for issue in &report.issues {
+                nice_plug::nice_log!("Import of {}: {issue}", path.display());
+            }
+
+            // Let the views know that the pattern has changed.
+            params.pattern_version.fetch_add(1, Ordering::Release);
+
+            Some(format!("Import: {report}"))
+        }
+        Err(error) => Some(format!("Import failed: {error}")),
+    }
+}
@@ -0,0 +1,211 @@
+//! Signals shared by the views of the editor.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32};
+
+use vizia_plug::vizia::prelude::*;
+
+use crate::AppParams;
+use crate::config::{MAX_NOTE_TRACKS, TRACKS};
+use crate::params::MAX_LAYERS;
+
+/// Signals for a value of each step of all tracks.
+pub type PerStep<T> = [[SyncSignal<Arc<T>>; 16]; TRACKS];
+
+/// Signals for a value of each track.
+pub type PerTrack<T> = [SyncSignal<Arc<T>>; TRACKS];
+
+/// Signals for the editor state and the values of the pattern.
+///
+/// Each value has exactly one signal, so all views showing the value are updated
+/// when it is changed in one of them.
+pub struct SharedSignals {
+    /// Number of the track selected for the lanes.
+    pub selected_track: SyncSignal<Arc<AtomicU32>>,
+
+    /// Lane shown for the selected track.
+    pub selected_lane: SyncSignal<Arc<AtomicU32>>,
+
+    /// States of the steps.
+    pub steps: PerStep<AtomicU32>,
+
+    /// Active lengths of the tracks.
+    pub lengths: PerTrack<AtomicU32>,
+
+    /// Trigger conditions of the steps.
+    pub conditions: PerStep<AtomicU32>,
+
+    /// Trigger probabilities of the steps.
+    pub probabilities: PerStep<AtomicU32>,
+
+    /// Note lengths of the steps.
+    pub gates: PerStep<AtomicU32>,
+
+    /// Timing offsets of the steps.
+    pub nudges: PerStep<AtomicI32>,
+
+    /// Number of hits of the steps.
+    pub ratchets: PerStep<AtomicU32>,
+
+    /// Velocity ramps of the ratchets.
+    pub ramps: PerStep<AtomicU32>,
+
+    /// Individual velocities of the steps.
+    pub velocities: PerStep<AtomicU32>,
+
+    /// Modes of the tracks.
+    pub modes: PerTrack<AtomicU32>,
+
+    /// Note numbers of the steps.
+    pub notes: PerStep<AtomicU32>,
+
+    /// Layered notes of the steps.
+    pub layers: PerStep<AtomicU32>,
+
+    /// Note numbers that can be layered on the steps of each track.
+    pub layer_notes: [[SyncSignal<Arc<AtomicU32>>; MAX_LAYERS]; TRACKS],
+
+    /// Flags if the euclidean rhythms of the tracks are enabled.
+    pub euclid_enabled: PerTrack<AtomicBool>,
+
+    /// Number of hits of the euclidean rhythms.
+    pub euclid_pulses: PerTrack<AtomicU32>,
+
+    /// Length in steps of the euclidean rhythms.
+    pub euclid_steps: PerTrack<AtomicU32>,
+
+    /// Rotation in steps of the euclidean rhythms.
+    pub euclid_rotations: PerTrack<AtomicU32>,
+
+    /// Notes sent by the tracks, except the accent track.
+    pub track_notes: [SyncSignal<Arc<AtomicU32>>; TRACKS - 1],
+
+    /// Output channels of the tracks, except the accent track.
+    pub track_channels: [SyncSignal<Arc<AtomicU32>>; TRACKS - 1],
+
+    /// Number of note tracks in use.
+    pub track_count: SyncSignal<Arc<AtomicU32>>,
+
+    /// Names entered for the note tracks, empty for the default labels.
+    pub track_names: [SyncSignal<String>; MAX_NOTE_TRACKS],
+
+    /// Choke groups of the note tracks, 0 for none.
+    pub choke_groups: [SyncSignal<Arc<AtomicU32>>; MAX_NOTE_TRACKS],
+
+    /// Flag if only one track of a choke group can be set on a step.
+    pub exclusive_chokes: SyncSignal<Arc<AtomicBool>>,
+
+    /// Flags if the steps are set on the accent lane of their track.
+    pub accents: PerStep<AtomicBool>,
+
+    /// Flags if the accent lanes of the note tracks are played.
+    pub accent_lanes: [SyncSignal<Arc<AtomicBool>>; MAX_NOTE_TRACKS],
+
+    /// Accent velocities of the accent lanes.
+    pub accent_velocities: [SyncSignal<Arc<AtomicU32>>; MAX_NOTE_TRACKS],
+}
+
+impl SharedSignals {
+    /// Returns a new instance with signals for the values of `params`.
+    pub fn new(params: &AppParams) -> Self {
+        let pattern = &params.pattern;
+
+        Self {
+            selected_track: SyncSignal::new(params.selected_track.clone()),
+            selected_lane: SyncSignal::new(params.selected_lane.clone()),
+            steps: per_step(&pattern.steps),
+            lengths: per_track(&pattern.lengths),
+            conditions: per_step(&pattern.conditions),
+            probabilities: per_step(&pattern.probabilities),
+            gates: per_step(&pattern.gates),
+            nudges: per_step(&pattern.nudges),
+            ratchets: per_step(&pattern.ratchets),
+            ramps: per_step(&pattern.ramps),
+            velocities: per_step(&pattern.velocities),
+            modes: per_track(&pattern.modes),
+            notes: per_step(&pattern.notes),
+            layers: per_step(&pattern.layers),
+            layer_notes: core::array::from_fn(|track| {
+                core::array::from_fn(|layer| {
+                    SyncSignal::new(pattern.layer_notes[track][layer].clone())
+                })
+            }),
+            euclid_enabled: per_track(&pattern.euclid_enabled),
+            euclid_pulses: per_track(&pattern.euclid_pulses),
+            euclid_steps: per_track(&pattern.euclid_steps),
+            euclid_rotations: per_track(&pattern.euclid_rotations),
+            track_notes: core::array::from_fn(|track| {
+                SyncSignal::new(params.note_map.notes[track].clone())
+            }),
+            track_channels: core::array::from_fn(|track| {
+                SyncSignal::new(params.note_map.channels[track].clone())
+            }),
+            track_count: SyncSignal::new(params.track_setup.count.clone()),
+            track_names: core::array::from_fn(|track| {
+                let name = params.track_setup.names[track].read();
+                SyncSignal::new(name.map(|name| name.clone()).unwrap_or_default())
+            }),
+            choke_groups: core::array::from_fn(|track| {
+                SyncSignal::new(params.track_setup.choke_groups[track].clone())
+            }),
+            exclusive_chokes: SyncSignal::new(params.track_setup.exclusive_chokes.clone()),
+            accents: per_step(&pattern.accents),
+            accent_lanes: core::array::from_fn(|track| {
+                SyncSignal::new(params.track_setup.accent_lanes[track].clone())
+            }),
+            accent_velocities: core::array::from_fn(|track| {
+                SyncSignal::new(params.track_setup.accent_velocities[track].clone())
+            }),
+        }
+    }
+}
+
+impl SharedSignals {
+    /// Notifies all views showing values of the pattern, e.g. after the pattern
+    /// was changed outside of the editor.
+    pub fn refresh(&self) {
+        notify(&self.lengths);
+        notify(&self.modes);
+        notify(&self.euclid_enabled);
+        notify(&self.euclid_pulses);
+        notify(&self.euclid_steps);
+        notify(&self.euclid_rotations);
+        notify(self.layer_notes.as_flattened());
+
+        for signals in [
+            &self.steps,
+            &self.conditions,
+            &self.probabilities,
+            &self.gates,
+            &self.ratchets,
+            &self.ramps,
+            &self.velocities,
+            &self.notes,
+            &self.layers,
+        ] {
+            notify(signals.as_flattened());
+        }
+
+        notify(self.nudges.as_flattened());
+        notify(self.accents.as_flattened());
+    }
+}
+
+/// Notifies the subscribers of signals without changing the values.
+fn notify<T: Send + Sync + 'static>(signals: &[SyncSignal<Arc<T>>]) {
+    for signal in signals {
+        signal.update(|_| {});
+    }
+}
+
+/// Returns signals for a value of each step of all tracks.
+fn per_step<T: Send + Sync + 'static>(values: &[[Arc<T>; 16]; TRACKS]) -> PerStep<T> {
+    core::array::from_fn(|track| {
+        core::array::from_fn(|step| SyncSignal::new(values[track][step].clone()))
+    })
+}
+
+/// Returns signals for a value of each track.
+fn per_track<T: Send + Sync + 'static>(values: &[Arc<T>; TRACKS]) -> PerTrack<T> {
+    core::array::from_fn(|track| SyncSignal::new(values[track].clone()))
+}
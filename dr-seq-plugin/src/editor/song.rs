@@ -0,0 +1,134 @@
+//! Chain of patterns for song mode.
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, AtomicU32};
+
+use vizia_plug::vizia::prelude::*;
+use vizia_plug::widgets::*;
+
+use dr_seq_engine::MAX_CHAIN_ENTRIES;
+
+use super::EditorEvent;
+use super::style::*;
+use crate::AppParams;
+use crate::config::BANK_SIZE;
+
+/// Maximum number of bars an entry of the chain can be repeated.
+const MAX_REPEATS: u32 = 64;
+
+/// Creates the chain.
+pub fn create(cx: &mut Context, params: Arc<AppParams>) {
+    let length = SyncSignal::new(params.chain.length.clone());
+    let looping = SyncSignal::new(params.chain.looping.clone());
+
+    HStack::new(cx, |cx| {
+        ParamButton::new(cx, &params.song_mode);
+        Element::new(cx).width(ELEMENT_SPACER_WIDTH);
+
+        for entry in 0..MAX_CHAIN_ENTRIES {
+            let signals = EntrySignals {
+                pattern: SyncSignal::new(params.chain.patterns[entry].clone()),
+                repeats: SyncSignal::new(params.chain.repeats[entry].clone()),
+                fill: SyncSignal::new(params.chain.fills[entry].clone()),
+            };
+            create_entry(cx, signals, length, entry);
+            Element::new(cx).width(Pixels(3.0));
+        }
+
+        Element::new(cx).width(ELEMENT_SPACER_WIDTH);
+        Label::new(cx, "LOOP")
+            .class("lane-label")
+            .bind(looping, move |handle| {
+                handle.toggle_class("selected", looping.get().load(Ordering::Relaxed));
+            })
+            .on_press(move |eh| {
+                looping.update(|l| {
+                    l.fetch_xor(true, Ordering::Relaxed);
+                });
+
+                // Send an event back to the engine.
+                eh.emit(EditorEvent::UpdateEngine);
+            });
+    })
+    .id("chain");
+}
+
+/// Signals for the settings of a chain entry.
+#[derive(Clone, Copy)]
+struct EntrySignals {
+    /// Number of the pattern, starting at 0.
+    pattern: SyncSignal<Arc<AtomicU32>>,
+
+    /// Number of bars the pattern is played.
+    repeats: SyncSignal<Arc<AtomicU32>>,
+
+    /// Fill mode of the entry.
+    fill: SyncSignal<Arc<AtomicBool>>,
+}
+
+/// Creates a single entry of the chain.
+///
+/// Clicking with the control key pressed sets the length of the chain to end at this entry.
+/// A left click selects the next pattern and a right click the previous one, with the shift
+/// key pressed the number of repeats is changed instead. Clicking with the alt key pressed
+/// toggles the fill mode.
+fn create_entry(
+    cx: &mut Context,
+    signals: EntrySignals,
+    length: SyncSignal<Arc<AtomicU32>>,
+    entry: usize,
+) {
+    let EntrySignals {
+        pattern,
+        repeats,
+        fill,
+    } = signals;
+
+    let update_text = move |handle: Handle<'_, Label>| {
+        let pattern_no = pattern.get().load(Ordering::Relaxed) + 1;
+        let repeat_count = repeats.get().load(Ordering::Relaxed);
+        handle.text(format!("{pattern_no}×{repeat_count}"));
+    };
+
+    Label::new(cx, "")
+        .class("chain-entry")
+        .bind(pattern, update_text)
+        .bind(repeats, update_text)
+        .bind(fill, move |handle| {
+            handle.toggle_class("fill", fill.get().load(Ordering::Relaxed));
+        })
+        .bind(length, move |handle| {
+            let length = length.get().load(Ordering::Relaxed) as usize;
+            handle.toggle_class("inactive", entry >= length);
+        })
+        .on_mouse_down(move |eh, button| {
+            let step = match button {
+                MouseButton::Left => 1,
+                MouseButton::Right => -1,
+                _ => return,
+            };
+
+            let modifiers = eh.modifiers();
+
+            if modifiers.contains(Modifiers::CTRL) {
+                length.update(|l| l.store(entry as u32 + 1, Ordering::Relaxed));
+            } else if modifiers.contains(Modifiers::ALT) {
+                fill.update(|f| {
+                    f.fetch_xor(true, Ordering::Relaxed);
+                });
+            } else if modifiers.contains(Modifiers::SHIFT) {
+                let value = repeats.get().load(Ordering::Relaxed) as i32 + step;
+                repeats.update(|r| {
+                    r.store(value.clamp(1, MAX_REPEATS as i32) as u32, Ordering::Relaxed)
+                });
+            } else {
+                let count = BANK_SIZE as i32;
+                let value = (pattern.get().load(Ordering::Relaxed) as i32 + step).rem_euclid(count);
+                pattern.update(|p| p.store(value as u32, Ordering::Relaxed));
+            }
+
+            // Send an event back to the engine.
+            eh.emit(EditorEvent::UpdateEngine);
+        });
+}
@@ -10,3 +10,15 @@ pub const TRACK_ROW_SPACER_HEIGHT: Units = Pixels(3.0);
 
 /// Width of spacer between various elements.
 pub const ELEMENT_SPACER_WIDTH: Units = Pixels(10.0);
+
+/// Distance in pixels before a mouse movement on a cell is treated as drag.
+pub const DRAG_THRESHOLD: f32 = 4.0;
+
+/// Drag distance in pixels for changing the probability by one percent.
+pub const DRAG_PIXELS_PER_PERCENT: f32 = 1.0;
+
+/// Drag distance in pixels for changing the timing offset by one pulse.
+pub const DRAG_PIXELS_PER_PULSE: f32 = 0.5;
+
+/// Distance in pixels the content of a cell is moved at the maximum timing offset.
+pub const NUDGE_INDICATOR_WIDTH: f32 = 5.0;
@@ -1,67 +1,93 @@
 //! Tracks with cells for each step.
 
 use std::sync::Arc;
-use std::sync::atomic::AtomicU32;
 use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32};
 
 use vizia_plug::vizia::prelude::*;
 
+use dr_seq_engine::{Gate, Ramp};
+
 use super::EditorEvent;
 use super::controls::*;
+use super::signals::SharedSignals;
 use super::style::*;
 use crate::AppParams;
 use crate::config::*;
-use crate::params::StepState;
+use crate::kits::{KITS, find_kit};
+use crate::params::{MAX_NUDGE, STEP_CONDITIONS, STEP_GATES, STEP_RAMPS, STEP_RATCHETS, StepState};
 
 /// Creates the tracks.
-pub fn create(cx: &mut Context, params: Arc<AppParams>) {
-    VStack::new(cx, |cx| {
-        for track in 0..TRACKS {
-            if track == TRACKS - 1 {
-                // Add some space before the accent track.
-                Element::new(cx).height(TRACK_ROW_SPACER_HEIGHT);
-                Element::new(cx).height(TRACK_ROW_SPACER_HEIGHT);
-            }
+pub fn create(cx: &mut Context, params: Arc<AppParams>, signals: &SharedSignals) {
+    // More tracks than fit into the editor can be in use, so they are scrolled.
+    ScrollView::new(cx, |cx| {
+        VStack::new(cx, |cx| {
+            for track in 0..TRACKS {
+                if track == TRACKS - 1 {
+                    // Add some space before the accent track.
+                    Element::new(cx).height(TRACK_ROW_SPACER_HEIGHT);
+                    Element::new(cx).height(TRACK_ROW_SPACER_HEIGHT);
+                }
 
-            create_track(cx, params.clone(), track);
-        }
-    })
-    .id("tracks");
-}
-
-/// Creates a single track.
-fn create_track(cx: &mut Context, params: Arc<AppParams>, track: usize) {
-    let enable_params = [
-        &params.track1_enable,
-        &params.track2_enable,
-        &params.track3_enable,
-        &params.track4_enable,
-        &params.track5_enable,
-        &params.track6_enable,
-        &params.track7_enable,
-        &params.track8_enable,
-    ];
-
-    let delay_params = [
-        &params.track1_delay,
-        &params.track2_delay,
-        &params.track3_delay,
-        &params.track4_delay,
-        &params.track5_delay,
-        &params.track6_delay,
-        &params.track7_delay,
-        &params.track8_delay,
-    ];
+                create_track(cx, params.clone(), signals, track);
+            }
+        })
+        .id("tracks");
+    });
+}
 
+/// Creates a single track, which is only shown while it is in use.
+fn create_track(cx: &mut Context, params: Arc<AppParams>, signals: &SharedSignals, track: usize) {
     let accent_track = track == TRACKS - 1;
+    let track_count = signals.track_count;
 
     VStack::new(cx, |cx| {
         HStack::new(cx, |cx| {
-            Label::new(cx, TRACK_LABELS[track]).width(Pixels(45.0));
+            if accent_track {
+                Label::new(cx, TRACK_LABELS[track]).width(Pixels(45.0));
+            } else {
+                // Clicking the label selects the track for the lanes.
+                let selected_track = signals.selected_track;
+                let name = signals.track_names[track];
+                Label::new(cx, "")
+                    .width(Pixels(45.0))
+                    .class("track-label")
+                    .bind(name, {
+                        let params = params.clone();
+                        move |handle| {
+                            handle.text(params.track_setup.name(track));
+                        }
+                    })
+                    .bind(selected_track, move |handle| {
+                        let selected = selected_track.get().load(Ordering::Relaxed) as usize;
+                        handle.toggle_class("selected", selected == track);
+                    })
+                    .on_press(move |_| {
+                        selected_track.update(|s| s.store(track as u32, Ordering::Relaxed));
+                    });
+            }
+
+            let length = signals.lengths[track];
 
             for step in 0..16 {
-                let signal = SyncSignal::new(params.pattern.steps[track][step].clone());
-                create_cell(cx, signal, accent_track);
+                let step_signals = StepSignals {
+                    state: signals.steps[track][step],
+                    condition: signals.conditions[track][step],
+                    probability: signals.probabilities[track][step],
+                    gate: signals.gates[track][step],
+                    nudge: signals.nudges[track][step],
+                    ratchets: signals.ratchets[track][step],
+                    ramp: signals.ramps[track][step],
+                    layers: signals.layers[track][step],
+                };
+                let choke_signals = ChokeSignals {
+                    track,
+                    steps: core::array::from_fn(|t| signals.steps[t][step]),
+                    groups: signals.choke_groups,
+                    track_count,
+                    exclusive: signals.exclusive_chokes,
+                };
+                create_cell(cx, step_signals, choke_signals, length, step, accent_track);
                 Element::new(cx).width(Pixels(3.0));
 
                 if step % 4 == 3 && step != 15 {
@@ -73,20 +99,377 @@ fn create_track(cx: &mut Context, params: Arc<AppParams>, track: usize) {
             if !accent_track {
                 HStack::new(cx, |cx| {
                     Element::new(cx).width(ELEMENT_SPACER_WIDTH);
-                    param_button(cx, enable_params[track]);
+                    param_button(cx, &params.tracks[track].enable);
+                    Element::new(cx).width(ELEMENT_SPACER_WIDTH);
+                    param_slider(cx, &params.tracks[track].delay);
                     Element::new(cx).width(ELEMENT_SPACER_WIDTH);
-                    param_slider(cx, delay_params[track]);
+                    param_slider(cx, &params.tracks[track].gate);
+                    Element::new(cx).width(ELEMENT_SPACER_WIDTH);
+                    create_note(cx, signals.track_notes[track]);
+                    Element::new(cx).width(Pixels(3.0));
+                    create_channel(cx, signals.track_channels[track]);
+                    Element::new(cx).width(Pixels(3.0));
+                    create_choke_group(cx, signals.choke_groups[track]);
                 })
                 .padding_top(Pixels(3.0));
             }
         });
+    })
+    .bind(track_count, move |handle| {
+        let used = accent_track || track < used_tracks(track_count);
+        handle.display(if used { Display::Flex } else { Display::None });
     });
 }
 
+/// Returns the number of note tracks in use.
+fn used_tracks(track_count: SyncSignal<Arc<AtomicU32>>) -> usize {
+    (track_count.get().load(Ordering::Relaxed) as usize).clamp(MIN_NOTE_TRACKS, MAX_NOTE_TRACKS)
+}
+
+/// Creates the note number of a track.
+///
+/// A left click raises the note and a right click lowers it, with the shift key pressed
+/// by an octave.
+fn create_note(cx: &mut Context, note: SyncSignal<Arc<AtomicU32>>) {
+    Label::new(cx, "")
+        .class("note-label")
+        .bind(note, move |handle| {
+            handle.text(format!("{}", note.get().load(Ordering::Relaxed)));
+        })
+        .on_mouse_down(move |eh, button| {
+            let step = match button {
+                MouseButton::Left => 1,
+                MouseButton::Right => -1,
+                _ => return,
+            };
+            let step = if eh.modifiers().contains(Modifiers::SHIFT) {
+                step * 12
+            } else {
+                step
+            };
+
+            // The note map is read when the notes are sent, so the engine needs no update.
+            let value = (note.get().load(Ordering::Relaxed) as i32 + step).clamp(0, 127);
+            note.update(|n| n.store(value as u32, Ordering::Relaxed));
+        });
+}
+
+/// Creates the output channel of a track.
+///
+/// A left click selects the next channel and a right click the previous one.
+fn create_channel(cx: &mut Context, channel: SyncSignal<Arc<AtomicU32>>) {
+    Label::new(cx, "")
+        .class("channel-label")
+        .bind(channel, move |handle| {
+            handle.text(format!("{}", channel.get().load(Ordering::Relaxed) + 1));
+        })
+        .on_mouse_down(move |_, button| {
+            let step = match button {
+                MouseButton::Left => 1,
+                MouseButton::Right => -1,
+                _ => return,
+            };
+
+            // The note map is read when the notes are sent, so the engine needs no update.
+            let value = (channel.get().load(Ordering::Relaxed) as i32 + step).rem_euclid(16);
+            channel.update(|c| c.store(value as u32, Ordering::Relaxed));
+        });
+}
+
+/// Creates the choke group of a track.
+///
+/// A left click selects the next group and a right click the previous one.
+fn create_choke_group(cx: &mut Context, group: SyncSignal<Arc<AtomicU32>>) {
+    Label::new(cx, "")
+        .class("choke-label")
+        .bind(group, move |handle| {
+            let text = match group.get().load(Ordering::Relaxed).min(CHOKE_GROUPS) {
+                0 => "-".to_string(),
+                n => char::from(b'A' + n as u8 - 1).to_string(),
+            };
+            handle.text(text);
+        })
+        .on_mouse_down(move |eh, button| {
+            let step = match button {
+                MouseButton::Left => 1,
+                MouseButton::Right => -1,
+                _ => return,
+            };
+
+            let count = CHOKE_GROUPS as i32 + 1;
+            let value = (group.get().load(Ordering::Relaxed) as i32 + step).rem_euclid(count);
+            group.update(|g| g.store(value as u32, Ordering::Relaxed));
+
+            // Send an event back to the engine.
+            eh.emit(EditorEvent::UpdateEngine);
+        });
+}
+
+/// Creates the switch for exclusive choke groups.
+///
+/// While it is on, setting a step clears the step on the other tracks of the choke group.
+pub fn create_choke_mode(cx: &mut Context, signals: &SharedSignals) {
+    let exclusive = signals.exclusive_chokes;
+    Label::new(cx, "EXCL")
+        .class("lane-label")
+        .padding_top(Pixels(5.0))
+        .bind(exclusive, move |handle| {
+            handle.toggle_class("selected", exclusive.get().load(Ordering::Relaxed));
+        })
+        .on_press(move |_| {
+            exclusive.update(|e| {
+                e.fetch_xor(true, Ordering::Relaxed);
+            });
+        });
+}
+
+/// Creates the selector for the kit, which sets the notes of all tracks at once.
+///
+/// A left click selects the next kit and a right click the previous one.
+/// The name is shown as long as the notes match the kit.
+pub fn create_kit_selector(cx: &mut Context, signals: &SharedSignals) {
+    let track_notes = signals.track_notes;
+    let notes = move || track_notes.map(|note| note.get().load(Ordering::Relaxed).min(127) as u8);
+
+    let mut handle = Label::new(cx, "")
+        .class("kit-name")
+        .on_mouse_down(move |_, button| {
+            let step = match button {
+                MouseButton::Left => 1,
+                MouseButton::Right => -1,
+                _ => return,
+            };
+
+            let kit_no = match find_kit(&notes()) {
+                Some(kit_no) => (kit_no as i32 + step).rem_euclid(KITS.len() as i32) as usize,
+                None => 0,
+            };
+
+            for (note, kit_note) in track_notes.iter().zip(KITS[kit_no].notes.iter().copied()) {
+                note.update(|n| n.store(kit_note as u32, Ordering::Relaxed));
+            }
+        });
+
+    for note in track_notes {
+        handle = handle.bind(note, move |handle| {
+            handle.text(find_kit(&notes()).map_or("Custom", |kit_no| KITS[kit_no].name));
+        });
+    }
+}
+
+/// Creates the selector for the number of note tracks and the name of the selected track.
+///
+/// A left click on the number adds a track and a right click removes the last one.
+pub fn create_track_setup(cx: &mut Context, params: Arc<AppParams>, signals: &SharedSignals) {
+    let track_count = signals.track_count;
+    let selected_track = signals.selected_track;
+
+    Label::new(cx, "")
+        .class("track-count")
+        .bind(track_count, move |handle| {
+            handle.text(format!("{}", used_tracks(track_count)));
+        })
+        .on_mouse_down(move |eh, button| {
+            let count = match button {
+                MouseButton::Left => used_tracks(track_count) + 1,
+                MouseButton::Right => used_tracks(track_count) - 1,
+                _ => return,
+            }
+            .clamp(MIN_NOTE_TRACKS, MAX_NOTE_TRACKS);
+            track_count.update(|c| c.store(count as u32, Ordering::Relaxed));
+
+            // Keep the lanes on a track that is shown.
+            if selected_track.get().load(Ordering::Relaxed) as usize >= count {
+                selected_track.update(|s| s.store(count as u32 - 1, Ordering::Relaxed));
+            }
+
+            // Send an event back to the engine.
+            eh.emit(EditorEvent::UpdateEngine);
+        });
+    Element::new(cx).width(ELEMENT_SPACER_WIDTH);
+
+    Label::new(cx, "Name")
+        .padding_top(Pixels(3.0))
+        .padding_right(Pixels(5.0));
+
+    // Only the text box of the selected track is shown.
+    for track in 0..MAX_NOTE_TRACKS {
+        let name = signals.track_names[track];
+        Textbox::new(cx, name)
+            .class("track-name")
+            .bind(selected_track, move |handle| {
+                let selected = selected_track.get().load(Ordering::Relaxed) as usize == track;
+                handle.display(if selected {
+                    Display::Flex
+                } else {
+                    Display::None
+                });
+            })
+            .on_edit({
+                let params = params.clone();
+                move |_, text| {
+                    params.track_setup.set_name(track, &text);
+                    name.update(|n| *n = text);
+                }
+            });
+    }
+}
+
+/// Signals for keeping a single track of a choke group set on a step.
+#[derive(Clone, Copy)]
+struct ChokeSignals {
+    /// Number of the track of the cell.
+    track: usize,
+
+    /// States of the same step on all tracks.
+    steps: [SyncSignal<Arc<AtomicU32>>; TRACKS],
+
+    /// Choke groups of the note tracks.
+    groups: [SyncSignal<Arc<AtomicU32>>; MAX_NOTE_TRACKS],
+
+    /// Number of note tracks in use.
+    track_count: SyncSignal<Arc<AtomicU32>>,
+
+    /// Flag if only one track of a choke group can be set on a step.
+    exclusive: SyncSignal<Arc<AtomicBool>>,
+}
+
+impl ChokeSignals {
+    /// Clears the step on the other tracks in use of the same choke group,
+    /// if the choke groups are exclusive.
+    fn clear_others(&self) {
+        if !self.exclusive.get().load(Ordering::Relaxed) {
+            return;
+        }
+
+        let group = |track: usize| {
+            self.groups.get(track).map_or(0, |group| {
+                group.get().load(Ordering::Relaxed).min(CHOKE_GROUPS)
+            })
+        };
+        let own_group = group(self.track);
+        if own_group == 0 {
+            return;
+        }
+
+        let count = used_tracks(self.track_count);
+        for (track, state) in self.steps.iter().enumerate().take(count) {
+            if track != self.track && group(track) == own_group {
+                state.update(|s| s.store(StepState::Off.into(), Ordering::Relaxed));
+            }
+        }
+    }
+}
+
+/// Signals for the settings of a single step.
+#[derive(Clone, Copy)]
+struct StepSignals {
+    /// State of the step.
+    state: SyncSignal<Arc<AtomicU32>>,
+
+    /// Trigger condition of the step.
+    condition: SyncSignal<Arc<AtomicU32>>,
+
+    /// Trigger probability of the step.
+    probability: SyncSignal<Arc<AtomicU32>>,
+
+    /// Note length of the step.
+    gate: SyncSignal<Arc<AtomicU32>>,
+
+    /// Timing offset of the step.
+    nudge: SyncSignal<Arc<AtomicI32>>,
+
+    /// Number of hits of the step.
+    ratchets: SyncSignal<Arc<AtomicU32>>,
+
+    /// Velocity ramp of the ratchets.
+    ramp: SyncSignal<Arc<AtomicU32>>,
+
+    /// Layered notes of the step.
+    layers: SyncSignal<Arc<AtomicU32>>,
+}
+
+/// Mouse gestures on a cell.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Gesture {
+    /// Click without movement.
+    #[default]
+    Click,
+
+    /// Vertical drag changing the probability.
+    Probability,
+
+    /// Horizontal drag changing the timing offset.
+    Nudge,
+}
+
+impl From<Gesture> for u32 {
+    fn from(value: Gesture) -> Self {
+        match value {
+            Gesture::Click => 0,
+            Gesture::Probability => 1,
+            Gesture::Nudge => 2,
+        }
+    }
+}
+
+impl From<u32> for Gesture {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => Gesture::Probability,
+            2 => Gesture::Nudge,
+            _ => Gesture::Click,
+        }
+    }
+}
+
 /// Creates a single cell.
-fn create_cell(cx: &mut Context, state: SyncSignal<Arc<AtomicU32>>, accent_step: bool) {
+///
+/// Clicking with the control key pressed sets the length of the track to end at this cell.
+/// Right-clicking cycles through the trigger conditions, with the alt key pressed through
+/// the note lengths, with the shift key pressed through the ratchet counts and with both
+/// keys pressed through the ratchet velocity ramps.
+/// Dragging vertically changes the trigger probability, dragging horizontally the timing offset.
+/// Setting a step clears it on the other tracks of an exclusive choke group.
+fn create_cell(
+    cx: &mut Context,
+    signals: StepSignals,
+    choke_signals: ChokeSignals,
+    length: SyncSignal<Arc<AtomicU32>>,
+    step: usize,
+    accent_step: bool,
+) {
+    let StepSignals {
+        state,
+        condition,
+        probability,
+        gate,
+        nudge,
+        ratchets,
+        ramp,
+        layers,
+    } = signals;
+
+    // Probability and timing offset at the start of a drag gesture.
+    let drag_start_probability = Arc::new(AtomicU32::new(100));
+    let drag_start_nudge = Arc::new(AtomicI32::new(0));
+
+    // Current gesture, which is a click until the mouse is moved.
+    let gesture = Arc::new(AtomicU32::new(Gesture::Click.into()));
+
     VStack::new(cx, |cx| {
-        Element::new(cx).class("content");
+        Element::new(cx)
+            .class("content")
+            .bind(probability, move |handle| {
+                let probability = probability.get().load(Ordering::Relaxed).min(100);
+                handle.opacity(0.2 + 0.8 * probability as f32 / 100.0);
+            })
+            .bind(nudge, move |handle| {
+                // Show the timing offset by moving the content off the center.
+                let nudge = nudge.get().load(Ordering::Relaxed);
+                let offset = nudge as f32 / MAX_NUDGE as f32 * NUDGE_INDICATOR_WIDTH;
+                handle.translate((Pixels(offset), Pixels(0.0)));
+            });
     })
     .class("step")
     .bind(state, move |handle| {
@@ -95,9 +478,169 @@ fn create_cell(cx: &mut Context, state: SyncSignal<Arc<AtomicU32>>, accent_step:
             .toggle_class("normal", step_state == StepState::Normal)
             .toggle_class("accent", step_state == StepState::Accent)
             .toggle_class("weak", step_state == StepState::Weak)
-            .toggle_class("ghost", step_state == StepState::Ghost);
+            .toggle_class("ghost", step_state == StepState::Ghost)
+            .toggle_class("custom", step_state == StepState::Custom);
+    })
+    .bind(length, move |handle| {
+        let length = length.get().load(Ordering::Relaxed) as usize;
+        handle.toggle_class("inactive", step >= length);
+    })
+    .bind(condition, move |handle| {
+        let condition = condition.get().load(Ordering::Relaxed);
+        handle.toggle_class("conditional", condition != 0);
+    })
+    .bind(gate, move |handle| {
+        let step_gate = STEP_GATES
+            .get(gate.get().load(Ordering::Relaxed) as usize)
+            .copied()
+            .flatten();
+        handle
+            .toggle_class("gate", matches!(step_gate, Some(Gate::Length(_))))
+            .toggle_class("tie", step_gate == Some(Gate::Tie));
+    })
+    .bind(nudge, move |handle| {
+        let nudge = nudge.get().load(Ordering::Relaxed);
+        handle.toggle_class("nudged", nudge != 0);
+    })
+    .bind(ratchets, move |handle| {
+        let ratchets = ratchets.get().load(Ordering::Relaxed);
+        handle.toggle_class("ratchet", ratchets > 1);
+    })
+    .bind(ramp, move |handle| {
+        let step_ramp = STEP_RAMPS
+            .get(ramp.get().load(Ordering::Relaxed) as usize)
+            .copied()
+            .unwrap_or_default();
+        handle
+            .toggle_class("ramp-up", step_ramp == Ramp::Up)
+            .toggle_class("ramp-down", step_ramp == Ramp::Down);
+    })
+    .bind(layers, move |handle| {
+        let layers = layers.get().load(Ordering::Relaxed);
+        handle.toggle_class("layered", layers != 0);
+    })
+    .on_mouse_down({
+        let drag_start_probability = drag_start_probability.clone();
+        let drag_start_nudge = drag_start_nudge.clone();
+        let gesture = gesture.clone();
+        move |eh, button| {
+            if button == MouseButton::Right {
+                let shift = eh.modifiers().contains(Modifiers::SHIFT);
+                let alt = eh.modifiers().contains(Modifiers::ALT);
+
+                match (shift, alt) {
+                    (false, false) => {
+                        let count = STEP_CONDITIONS.len() as u32;
+                        let step_condition = condition.get().load(Ordering::Relaxed) % count;
+                        condition
+                            .update(|c| c.store((step_condition + 1) % count, Ordering::Relaxed));
+                    }
+                    (false, true) => {
+                        let count = STEP_GATES.len() as u32;
+                        let step_gate = gate.get().load(Ordering::Relaxed) % count;
+                        gate.update(|g| g.store((step_gate + 1) % count, Ordering::Relaxed));
+                    }
+                    (true, false) => {
+                        let step_ratchets = ratchets.get().load(Ordering::Relaxed);
+                        let index = STEP_RATCHETS
+                            .iter()
+                            .position(|r| *r == step_ratchets)
+                            .unwrap_or_default();
+                        let new_ratchets = STEP_RATCHETS[(index + 1) % STEP_RATCHETS.len()];
+                        ratchets.update(|r| r.store(new_ratchets, Ordering::Relaxed));
+                    }
+                    (true, true) => {
+                        let count = STEP_RAMPS.len() as u32;
+                        let step_ramp = ramp.get().load(Ordering::Relaxed) % count;
+                        ramp.update(|r| r.store((step_ramp + 1) % count, Ordering::Relaxed));
+                    }
+                }
+
+                eh.emit(EditorEvent::UpdateEngine);
+                return;
+            }
+
+            if eh.modifiers().contains(Modifiers::CTRL) {
+                length.update(|l| l.store(step as u32 + 1, Ordering::Relaxed));
+                eh.emit(EditorEvent::UpdateEngine);
+                return;
+            }
+
+            // The step state is toggled on release, unless the gesture turns into a drag.
+            drag_start_probability.store(
+                probability.get().load(Ordering::Relaxed).min(100),
+                Ordering::Relaxed,
+            );
+            drag_start_nudge.store(nudge.get().load(Ordering::Relaxed), Ordering::Relaxed);
+            gesture.store(Gesture::Click.into(), Ordering::Relaxed);
+            eh.capture();
+        }
+    })
+    .on_mouse_move({
+        let gesture = gesture.clone();
+        move |eh, x, y| {
+            if eh.mouse().left.state != MouseButtonState::Pressed
+                || eh.modifiers().contains(Modifiers::CTRL)
+            {
+                return;
+            }
+
+            let (down_x, down_y) = eh.mouse().left.pos_down;
+            let distance_x = x - down_x;
+            let distance_y = down_y - y;
+
+            let mut current_gesture = Gesture::from(gesture.load(Ordering::Relaxed));
+
+            if current_gesture == Gesture::Click {
+                // The gesture is determined by the direction of the first movement
+                // that exceeds the threshold.
+                if distance_x.abs() >= DRAG_THRESHOLD && distance_x.abs() > distance_y.abs() {
+                    current_gesture = Gesture::Nudge;
+                } else if distance_y.abs() >= DRAG_THRESHOLD {
+                    current_gesture = Gesture::Probability;
+                } else {
+                    return;
+                }
+                gesture.store(current_gesture.into(), Ordering::Relaxed);
+            }
+
+            match current_gesture {
+                Gesture::Probability => {
+                    let start = drag_start_probability.load(Ordering::Relaxed) as f32;
+                    let new_probability =
+                        (start + distance_y / DRAG_PIXELS_PER_PERCENT).clamp(0.0, 100.0);
+                    probability
+                        .update(|p| p.store(new_probability.round() as u32, Ordering::Relaxed));
+                }
+                Gesture::Nudge => {
+                    let start = drag_start_nudge.load(Ordering::Relaxed) as f32;
+                    let new_nudge = (start + distance_x / DRAG_PIXELS_PER_PULSE)
+                        .clamp(-MAX_NUDGE as f32, MAX_NUDGE as f32);
+                    nudge.update(|n| n.store(new_nudge.round() as i32, Ordering::Relaxed));
+                }
+                Gesture::Click => {}
+            }
+        }
     })
-    .on_mouse_down(move |eh, _| {
+    .on_mouse_up(move |eh, button| {
+        if button != MouseButton::Left {
+            return;
+        }
+
+        eh.release();
+
+        let current_gesture = Gesture::from(gesture.swap(Gesture::Click.into(), Ordering::Relaxed));
+
+        if current_gesture != Gesture::Click {
+            // Send the changed probability or timing offset to the engine.
+            eh.emit(EditorEvent::UpdateEngine);
+            return;
+        }
+
+        if eh.modifiers().contains(Modifiers::CTRL) {
+            return;
+        }
+
         let shift = eh.modifiers().contains(Modifiers::SHIFT);
         let alt = eh.modifiers().contains(Modifiers::ALT);
 
@@ -121,6 +664,9 @@ fn create_cell(cx: &mut Context, state: SyncSignal<Arc<AtomicU32>>, accent_step:
         }
 
         state.update(|s| s.store(new_state.into(), Ordering::Relaxed));
+        if new_state != StepState::Off {
+            choke_signals.clear_others();
+        }
 
         // Send an event back to the engine.
         eh.emit(EditorEvent::UpdateEngine);
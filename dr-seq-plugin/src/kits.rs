@@ -0,0 +1,62 @@
+//! Templates of note maps for common drum machines and samplers.
+//!
+//! The notes are listed in the order of the default track labels, starting with BD, SD, CHH,
+//! PHH, OHH, WD, MT, SFX. Instruments without a matching sound use the closest one, e.g. the
+//! closed hihat for the pedal hihat. Tracks beyond the notes of a kit keep their notes.
+
+use crate::config::TRACK_NOTES;
+
+/// Note map of a kit.
+#[derive(Debug)]
+pub struct Kit {
+    /// Name shown in the editor.
+    pub name: &'static str,
+
+    /// Note numbers of the first tracks.
+    pub notes: &'static [u8],
+}
+
+/// Available kits, the first one is the default.
+pub static KITS: [Kit; 8] = [
+    Kit {
+        name: "General MIDI",
+        notes: &TRACK_NOTES,
+    },
+    Kit {
+        name: "Roland TR-8S",
+        notes: &[36, 38, 42, 42, 46, 37, 47, 49, 43, 50, 37, 39],
+    },
+    Kit {
+        name: "Elektron Rytm",
+        notes: &[36, 38, 42, 42, 46, 37, 43, 49, 41, 45, 37, 39, 56],
+    },
+    Kit {
+        name: "Korg Volca Beats",
+        notes: &[36, 38, 42, 42, 46, 75, 50, 49, 43, 50, 75, 39],
+    },
+    Kit {
+        name: "Behringer RD-8",
+        notes: &[36, 40, 42, 42, 46, 37, 47, 49, 45, 50, 37, 39, 56],
+    },
+    Kit {
+        name: "Akai MPC",
+        notes: &[36, 38, 42, 44, 46, 37, 48, 49],
+    },
+    Kit {
+        name: "Drum Rack C1",
+        notes: &[
+            36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51,
+        ],
+    },
+    Kit {
+        name: "Chromatic C3",
+        notes: &[
+            60, 61, 62, 63, 64, 65, 66, 67, 68, 69, 70, 71, 72, 73, 74, 75,
+        ],
+    },
+];
+
+/// Returns the index of the kit with the same notes on its tracks.
+pub fn find_kit(notes: &[u8]) -> Option<usize> {
+    KITS.iter().position(|kit| notes.starts_with(kit.notes))
+}
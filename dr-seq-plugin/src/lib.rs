@@ -3,8 +3,12 @@
 mod clock;
 mod config;
 mod editor;
+mod kits;
+mod library;
+mod midi_file;
 mod params;
 mod presets;
+mod state;
 
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -12,13 +16,12 @@ use std::sync::mpsc;
 
 use nice_plug::prelude::*;
 
-use dr_seq_engine::{Pattern, Pitch, StepEvent, Track, TrackEvent, TrackParams, Velocity};
+use dr_seq_engine::{Chain, Pitch, Sequencer, SequencerEvent};
 
 use clock::Clock;
 use config::*;
 use editor::EditorEvent;
-use params::{AppParams, StepState};
-use presets::load_preset;
+use params::AppParams;
 
 /// Main plugin struct.
 pub struct App {
@@ -34,14 +37,21 @@ pub struct App {
     /// Flag if transport is playing.
     playing: bool,
 
-    /// Individual tracks.
-    tracks: [Track; TRACKS],
+    /// Sequencer playing the tracks.
+    sequencer: Sequencer<TRACKS, 16>,
 
-    /// Patterns for the tracks.
-    patterns: [Pattern<16>; TRACKS],
+    /// Chain of patterns for song mode.
+    chain: Chain,
 
-    /// Step repeats for the tracks.
-    step_repeats: [bool; TRACKS],
+    /// Flag set by parameter changes that require an engine update.
+    params_changed: Arc<AtomicBool>,
+
+    /// Note numbers and channels of the sounding notes by track and pitch slot,
+    /// see `pitch_slot`.
+    ///
+    /// Note offs are sent with the note number and channel of their note on, so notes
+    /// don't hang when the note map is changed while they are sounding.
+    sounding_notes: [[Option<(u8, u8)>; PITCH_SLOTS]; TRACKS],
 }
 
 impl Default for App {
@@ -49,14 +59,17 @@ impl Default for App {
         let update_engine = Arc::new(AtomicBool::new(false));
         let editor_channel: (mpsc::SyncSender<EditorEvent>, mpsc::Receiver<EditorEvent>) =
             mpsc::sync_channel(64);
+        let mut sequencer = Sequencer::new();
+        sequencer.set_repeat_priority(&REPEAT_PRIORITY);
         Self {
             params: Arc::new(AppParams::new(update_engine.clone())),
             editor_event_sender: editor_channel.0,
             editor_event_receiver: editor_channel.1,
             playing: false,
-            tracks: core::array::from_fn(|_| Track::new()),
-            patterns: core::array::from_fn(|_| Pattern::<16>::new()),
-            step_repeats: [false; TRACKS],
+            sequencer,
+            chain: Chain::new(),
+            params_changed: update_engine,
+            sounding_notes: [[None; PITCH_SLOTS]; TRACKS],
         }
     }
 }
@@ -123,61 +136,55 @@ impl Plugin for App {
         _aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        if let Ok(event) = self.editor_event_receiver.try_recv() {
+        // Multiple events can be pending while values are drawn in the editor,
+        // but the engine must be updated only once.
+        let mut update_engine = self.params_changed.swap(false, Ordering::Acquire);
+
+        while let Ok(event) = self.editor_event_receiver.try_recv() {
             match event {
                 EditorEvent::UpdateEngine => {
-                    self.update_engine();
-                }
-                EditorEvent::LoadPreset(preset_no) => {
-                    load_preset(preset_no, self.params.clone());
-                    self.update_engine();
+                    update_engine = true;
                 }
             }
         }
 
+        if update_engine {
+            self.update_engine();
+        }
+
         let playing = context.transport().playing;
 
+        // While the transport is stopped, a newly selected pattern is activated immediately.
+        // In song mode, the pattern is determined by the chain once playback starts.
+        if !playing
+            && !self.params.song_mode.value()
+            && self.selected_pattern() != self.active_pattern()
+        {
+            self.switch_pattern(self.selected_pattern());
+        }
+
         if playing != self.playing {
             self.playing = playing;
-            if !playing {
+            if playing {
+                // Restart loop counting for the trigger conditions and the random
+                // sequence for the probabilities, so playback is reproducible.
+                self.sequencer.start(self.params.seed.value() as u32);
+            } else {
                 // When transport stops, any scheduled note offs should be sent immediately.
-                for track in self.tracks.as_mut() {
-                    track.flush();
-                }
-                for (n, track) in self.tracks.as_mut().iter_mut().enumerate() {
-                    while let Some(event) = track.next_event() {
-                        let note = TRACK_NOTES[n];
-                        if let TrackEvent::StepEvent(_, StepEvent::NoteOff { pitch }) = event {
-                            let event = NoteEvent::NoteOff {
-                                timing: 0,
-                                voice_id: None,
-                                channel: 0,
-                                note: match pitch {
-                                    Pitch::Default => note,
-                                    Pitch::Custom(pitch) => pitch as u8,
-                                    _ => note,
-                                },
-                                velocity: 0.0,
-                            };
-                            context.send_event(event)
-                        }
-                    }
-                }
+                self.sequencer.stop();
+                self.send_events(0, context);
             }
         }
 
+        // The tempo is kept for rendering outside of the audio thread, e.g. for export.
+        if let Some(tempo) = context.transport().tempo {
+            self.params.tempo.store(tempo, Ordering::Relaxed);
+        }
+
         let ppq = CLOCK_PPQ as f64;
         let clock = Clock::new(buffer, context.transport(), ppq);
 
-        // Convert the velocity values from 0-127 into 0.0-1.0 range.
-        let default_velocity = self.params.normal_velocity.value() as f32 / 127.0;
-        let accent_velocity = if self.params.accent_vel_mode.value() {
-            self.params.accent_velocity.value() as f32 / 127.0
-        } else {
-            (default_velocity + self.params.accent_velocity.value() as f32 / 127.0).clamp(0.0, 1.0)
-        };
-        let weak_velocity = self.params.weak_velocity.value() as f32 / 127.0;
-        let ghost_velocity = self.params.ghost_velocity.value() as f32 / 127.0;
+        let mut sequencer_params = self.params.sequencer_params();
 
         // Iterate over pulses generated by the clock.
         for (pulse_no, timing) in clock {
@@ -190,152 +197,49 @@ impl Plugin for App {
                     .store(current_step, Ordering::Relaxed);
             }
 
-            let mut track_ppq = CLOCK_PPQ;
+            let track_ppq = sequencer_params.manglers.ppq(CLOCK_PPQ);
 
-            // The FAST mangler doubles the speed by halving the ppq.
-            if self.params.mangler_fast.value() {
-                track_ppq /= 2;
-            }
-
-            // The SLOW mangler halves the speed by doubling the ppq.
-            if self.params.mangler_slow.value() {
-                track_ppq *= 2;
-            }
-
-            let mut track_params = TrackParams {
-                swing: self.params.swing.value() * CLOCK_PPQ as i32 / 8 / 100,
-                shift: if self.params.mangler_swag.value() {
-                    1
-                } else {
-                    0
-                },
-                reverse: self.params.mangler_mirror.value(),
-                triplet: self.params.triplet.value(),
-                ..Default::default()
+            // In song mode, the pattern follows the chain. The position is derived from
+            // the bar number, so a jump of the song position lands on the right entry.
+            let song_position = if self.params.song_mode.value() {
+                self.chain.position(pulse_no / (CLOCK_PPQ * 4))
+            } else {
+                None
             };
 
-            // The HACK mangler re-sorts the order of the steps.
-            if self.params.mangler_hack.value() {
-                track_params.resort_fn = Some(|step| {
-                    let base = step / 8 * 8;
-                    let sub = step % 8;
-                    base + match sub {
-                        0 => 0,
-                        1 => 3,
-                        2 => 1,
-                        3 => 7,
-                        4 => 2,
-                        5 => 6,
-                        6 => 4,
-                        7 => 5,
-                        _ => 0,
-                    }
-                });
-            }
-
-            let sole_enabled = self.params.mangler_sole.value();
-            let flame_enabled = self.params.mangler_flame.value();
-            let mut skip_notes = false;
-
-            for (n, track) in self.tracks.iter_mut().enumerate() {
-                track_params.enable = match n {
-                    0 => self.params.track1_enable.value(),
-                    1 => self.params.track2_enable.value(),
-                    2 => self.params.track3_enable.value(),
-                    3 => self.params.track4_enable.value(),
-                    4 => self.params.track5_enable.value(),
-                    5 => self.params.track6_enable.value(),
-                    6 => self.params.track7_enable.value(),
-                    7 => self.params.track8_enable.value(),
-                    _ => false,
-                };
-                track_params.delay = match n {
-                    0 => self.params.track1_delay.value(),
-                    1 => self.params.track2_delay.value(),
-                    2 => self.params.track3_delay.value(),
-                    3 => self.params.track4_delay.value(),
-                    4 => self.params.track5_delay.value(),
-                    5 => self.params.track6_delay.value(),
-                    6 => self.params.track7_delay.value(),
-                    7 => self.params.track8_delay.value(),
-                    _ => 0,
-                };
-                track_params.repeat = flame_enabled && self.step_repeats[n];
-
-                if !track_params.enable {
-                    // Clear step repeats on disabled tracks.
-                    self.step_repeats[n] = false;
+            if let Some(position) = song_position {
+                let pattern_no = (position.entry.pattern as usize).min(BANK_SIZE - 1);
+                if pattern_no != self.active_pattern() {
+                    self.switch_pattern(pattern_no);
                 }
+            } else if self.selected_pattern() != self.active_pattern() {
+                // A newly selected pattern is activated at the start of the next bar or,
+                // depending on the switch mode, the next loop of the longest track.
+                let switch_pulses = if self.params.switch_mode.value() {
+                    let length = self
+                        .sequencer
+                        .patterns()
+                        .iter()
+                        .enumerate()
+                        .filter(|(n, _)| self.params.track_setup.is_active(*n))
+                        .map(|(_, p)| p.length())
+                        .max();
+                    length.unwrap_or(16) * track_ppq / 4
+                } else {
+                    CLOCK_PPQ * 4
+                };
 
-                track.update(pulse_no, track_ppq, self.patterns[n].steps(), &track_params);
-
-                while let Some(event) = track.next_event() {
-                    // Turn track events into corresponding MIDI messages.
-                    let note = TRACK_NOTES[n];
-                    match event {
-                        TrackEvent::StepEvent(step, StepEvent::NoteOn { pitch, vel }) => {
-                            let accent = self.patterns[ACCENT_TRACK as usize].step(step).enabled();
-                            let event = NoteEvent::NoteOn {
-                                timing,
-                                voice_id: None,
-                                channel: 0,
-                                note: match pitch {
-                                    Pitch::Default => note,
-                                    Pitch::Custom(pitch) => pitch as u8,
-                                    _ => note,
-                                },
-                                velocity: match vel {
-                                    Velocity::Accent => accent_velocity,
-                                    Velocity::Weak => weak_velocity,
-                                    Velocity::Ghost => ghost_velocity,
-                                    _ => {
-                                        if accent {
-                                            accent_velocity
-                                        } else {
-                                            default_velocity
-                                        }
-                                    }
-                                },
-                            };
-                            if !skip_notes {
-                                context.send_event(event);
-                                if sole_enabled {
-                                    skip_notes = true;
-                                }
-                                if flame_enabled {
-                                    self.step_repeats[n] = true;
-                                }
-                            }
-                        }
-                        TrackEvent::StepEvent(_, StepEvent::NoteOff { pitch }) => {
-                            let event = NoteEvent::NoteOff {
-                                timing,
-                                voice_id: None,
-                                channel: 0,
-                                note: match pitch {
-                                    Pitch::Default => note,
-                                    Pitch::Custom(pitch) => pitch as u8,
-                                    _ => note,
-                                },
-                                velocity: 0.0,
-                            };
-                            context.send_event(event)
-                        }
-                        _ => {}
-                    }
+                if pulse_no.is_multiple_of(switch_pulses.max(1)) {
+                    self.switch_pattern(self.selected_pattern());
                 }
             }
 
-            // Make sure only one track has repeat enabled.
-            let repeat_priority = [1, 2, 3, 4, 5, 6, 0, 7];
-            let mut disable_repeat = false;
-            for n in repeat_priority {
-                if disable_repeat {
-                    self.step_repeats[n] = false;
-                } else if self.step_repeats[n] {
-                    disable_repeat = true;
-                }
-            }
+            sequencer_params.fill = self.params.fill.value()
+                || song_position.is_some_and(|position| position.entry.fill);
+
+            self.sequencer
+                .update(pulse_no, CLOCK_PPQ, &sequencer_params);
+            self.send_events(timing, context);
         }
 
         // Pass thru any incoming MIDI message.
@@ -348,29 +252,111 @@ impl Plugin for App {
 }
 
 impl App {
-    /// Update the engine with the parameters from the editor or host.
-    fn update_engine(&mut self) {
-        for t in 0..TRACKS {
-            for (s, step) in self.patterns[t].steps_mut().iter_mut().enumerate() {
-                let state =
-                    StepState::from(self.params.pattern.steps[t][s].load(Ordering::Relaxed));
-                if state != StepState::Off {
-                    step.enable();
-                    step.set_event(Some(StepEvent::NoteOn {
-                        pitch: Pitch::Default,
-                        vel: match state {
-                            StepState::Accent => Velocity::Accent,
-                            StepState::Weak => Velocity::Weak,
-                            StepState::Ghost => Velocity::Ghost,
-                            _ => Velocity::Default,
-                        },
-                    }));
-                } else {
-                    step.disable();
+    /// Returns the number of the pattern selected for playback, starting at 0.
+    fn selected_pattern(&self) -> usize {
+        (self.params.pattern_select.value() - 1).clamp(0, BANK_SIZE as i32 - 1) as usize
+    }
+
+    /// Returns the number of the pattern that is playing, starting at 0.
+    fn active_pattern(&self) -> usize {
+        (self.params.bank.active.load(Ordering::Relaxed) as usize).min(BANK_SIZE - 1)
+    }
+
+    /// Stores the playing pattern in the bank and activates another one.
+    fn switch_pattern(&mut self, pattern_no: usize) {
+        let bank = &self.params.bank;
+
+        bank.patterns[self.active_pattern()].copy_from(&self.params.pattern);
+        self.params.pattern.copy_from(&bank.patterns[pattern_no]);
+        bank.active.store(pattern_no as u32, Ordering::Relaxed);
+
+        // Let the editor know that the shown pattern has changed.
+        self.params.pattern_version.fetch_add(1, Ordering::Release);
+
+        self.update_engine();
+    }
+
+    /// Sends the pending events of the sequencer as MIDI messages.
+    /// - `timing`: Sample offset of the messages within the buffer.
+    fn send_events(&mut self, timing: u32, context: &mut impl ProcessContext<Self>) {
+        while let Some(event) = self.sequencer.next_event() {
+            match event {
+                SequencerEvent::NoteOn {
+                    track,
+                    pitch,
+                    velocity,
+                } => {
+                    let note = midi_note(pitch, self.params.note_map.note(track));
+                    let channel = self.params.note_map.channel(track);
+                    self.sounding_notes[track][pitch_slot(pitch)] = Some((note, channel));
+
+                    context.send_event(NoteEvent::NoteOn {
+                        timing,
+                        voice_id: None,
+                        channel,
+                        note,
+                        velocity,
+                    })
                 }
+                SequencerEvent::NoteOff { track, pitch } => {
+                    let (note, channel) = self.sounding_notes[track][pitch_slot(pitch)]
+                        .take()
+                        .unwrap_or_else(|| {
+                            let note_map = &self.params.note_map;
+                            (
+                                midi_note(pitch, note_map.note(track)),
+                                note_map.channel(track),
+                            )
+                        });
+
+                    context.send_event(NoteEvent::NoteOff {
+                        timing,
+                        voice_id: None,
+                        channel,
+                        note,
+                        velocity: 0.0,
+                    })
+                }
+                _ => {}
             }
         }
     }
+
+    /// Update the engine with the parameters from the editor or host.
+    fn update_engine(&mut self) {
+        self.chain = self.params.chain.to_chain();
+
+        self.params.pattern.update_tracks(
+            self.sequencer.patterns_mut(),
+            self.params.scale(),
+            self.params.root.value(),
+        );
+    }
+}
+
+/// Number of slots for the sounding notes of a track, one per note number and one
+/// for the default note of the track.
+const PITCH_SLOTS: usize = 129;
+
+/// Returns the slot of a pitch in the sounding notes of a track.
+fn pitch_slot(pitch: Pitch) -> usize {
+    match pitch {
+        Pitch::Custom(pitch) => pitch.clamp(0, 127) as usize,
+        _ => PITCH_SLOTS - 1,
+    }
+}
+
+/// Returns the MIDI note number for a pitch.
+/// - `pitch`: Pitch of the event.
+/// - `note`: Note number of the track used for the default pitch.
+///
+/// Custom pitches are clamped to the valid range of 0-127.
+fn midi_note(pitch: Pitch, note: u8) -> u8 {
+    match pitch {
+        Pitch::Default => note,
+        Pitch::Custom(pitch) => pitch.clamp(0, 127) as u8,
+        _ => note,
+    }
 }
 
 impl ClapPlugin for App {
@@ -0,0 +1,346 @@
+//! Library of presets stored on disk.
+//!
+//! User presets are stored as JSON files in a per-user directory. Each file contains the
+//! name, category and tags shown in the browser and the pattern in the same versioned format
+//! as the persisted state, so presets saved by older versions are migrated when loaded.
+//!
+//! The factory presets are listed as read-only entries in front of the user presets.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::DATA_DIR;
+use crate::params::{AppParams, Pattern};
+use crate::presets::{FACTORY_PRESETS, load_preset};
+use crate::state::Versioned;
+
+/// File extension of user presets.
+pub const PRESET_EXTENSION: &str = "json";
+
+/// Information about a preset shown in the browser.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresetInfo {
+    /// Name of the preset.
+    pub name: String,
+
+    /// Category the preset is listed in.
+    #[serde(default)]
+    pub category: String,
+
+    /// Keywords describing the preset.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl PresetInfo {
+    /// Returns the tags as comma-separated text.
+    pub fn tags_text(&self) -> String {
+        self.tags.join(", ")
+    }
+
+    /// Returns the tags from comma-separated text, skipping empty ones.
+    pub fn parse_tags(text: &str) -> Vec<String> {
+        text.split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(String::from)
+            .collect()
+    }
+}
+
+/// Origin of a preset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresetSource {
+    /// Factory preset with its number.
+    Factory(usize),
+
+    /// User preset with the path of its file.
+    User(PathBuf),
+}
+
+/// Entry of the library.
+#[derive(Debug, Clone)]
+pub struct PresetEntry {
+    /// Information shown in the browser.
+    pub info: PresetInfo,
+
+    /// Origin of the preset.
+    pub source: PresetSource,
+}
+
+impl PresetEntry {
+    /// Returns if the preset can't be renamed or deleted.
+    pub fn is_read_only(&self) -> bool {
+        matches!(self.source, PresetSource::Factory(_))
+    }
+}
+
+/// Contents of a preset file.
+#[derive(Serialize, Deserialize)]
+struct PresetFile<P> {
+    /// Information shown in the browser.
+    #[serde(flatten)]
+    info: PresetInfo,
+
+    /// Pattern with the version of its layout.
+    pattern: P,
+}
+
+/// Error on an operation of the library.
+#[derive(Debug)]
+pub enum LibraryError {
+    /// No directory for user presets is known on this system.
+    NoDirectory,
+
+    /// The entry is a factory preset.
+    ReadOnly,
+
+    /// There is no entry with the index.
+    InvalidIndex,
+
+    /// The name of the preset is empty.
+    EmptyName,
+
+    /// Reading or writing a file failed.
+    Io(std::io::Error),
+
+    /// The contents of a file are invalid.
+    Format(serde_json::Error),
+}
+
+impl std::fmt::Display for LibraryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoDirectory => write!(f, "no directory for user presets"),
+            Self::ReadOnly => write!(f, "factory presets can't be changed"),
+            Self::InvalidIndex => write!(f, "no preset selected"),
+            Self::EmptyName => write!(f, "preset name is empty"),
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Format(error) => write!(f, "invalid preset file: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for LibraryError {}
+
+impl From<std::io::Error> for LibraryError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for LibraryError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Format(error)
+    }
+}
+
+/// Library of factory and user presets.
+#[derive(Debug)]
+pub struct Library {
+    /// Directory of the user presets.
+    dir: Option<PathBuf>,
+
+    /// Entries, factory presets first, then user presets ordered by category and name.
+    entries: Vec<PresetEntry>,
+}
+
+impl Library {
+    /// Returns the library with the presets of the user directory.
+    pub fn open() -> Self {
+        let mut library = Self {
+            dir: dirs::data_dir().map(|dir| dir.join(DATA_DIR).join("presets")),
+            entries: Vec::new(),
+        };
+        library.refresh();
+
+        library
+    }
+
+    /// Returns the entries.
+    pub fn entries(&self) -> &[PresetEntry] {
+        &self.entries
+    }
+
+    /// Reads the entries again from the directory.
+    ///
+    /// Files that can't be read are skipped.
+    pub fn refresh(&mut self) {
+        self.entries = FACTORY_PRESETS
+            .iter()
+            .enumerate()
+            .map(|(n, preset)| PresetEntry {
+                info: PresetInfo {
+                    name: preset.name.into(),
+                    category: preset.category.into(),
+                    tags: preset.tags.iter().map(|&tag| tag.into()).collect(),
+                },
+                source: PresetSource::Factory(n),
+            })
+            .collect();
+
+        let Some(files) = self
+            .dir
+            .as_ref()
+            .and_then(|dir| std::fs::read_dir(dir).ok())
+        else {
+            return;
+        };
+
+        let mut user_entries: Vec<_> = files
+            .filter_map(|file| file.ok().map(|file| file.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == PRESET_EXTENSION))
+            .filter_map(|path| match read_info(&path) {
+                Ok(info) => Some(PresetEntry {
+                    info,
+                    source: PresetSource::User(path),
+                }),
+                Err(error) => {
+                    nice_plug::nice_log!("Skipping preset {}: {error}", path.display());
+                    None
+                }
+            })
+            .collect();
+
+        user_entries.sort_by(|a, b| {
+            (&a.info.category, &a.info.name).cmp(&(&b.info.category, &b.info.name))
+        });
+        self.entries.extend(user_entries);
+    }
+
+    /// Returns the index of the entry with a source.
+    pub fn position(&self, source: &PresetSource) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.source == *source)
+    }
+
+    /// Loads the pattern of an entry into the pattern that is playing.
+    pub fn load(&self, index: usize, params: &AppParams) -> Result<(), LibraryError> {
+        let entry = self.entries.get(index).ok_or(LibraryError::InvalidIndex)?;
+
+        match &entry.source {
+            PresetSource::Factory(preset_no) => load_preset(*preset_no, params),
+            PresetSource::User(path) => {
+                let data = std::fs::read(path)?;
+                let file: PresetFile<Versioned<Pattern>> = serde_json::from_slice(&data)?;
+                params.pattern.copy_from(&file.pattern);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Saves the pattern that is playing as new user preset.
+    ///
+    /// Returns the index of the new entry.
+    pub fn save(&mut self, info: PresetInfo, params: &AppParams) -> Result<usize, LibraryError> {
+        let dir = self.dir.clone().ok_or(LibraryError::NoDirectory)?;
+        if info.name.trim().is_empty() {
+            return Err(LibraryError::EmptyName);
+        }
+
+        std::fs::create_dir_all(&dir)?;
+        let path = unused_path(&dir, &info.name);
+        write_file(
+            &path,
+            &PresetFile {
+                info,
+                pattern: &params.pattern,
+            },
+        )?;
+
+        self.refresh();
+        self.position(&PresetSource::User(path))
+            .ok_or(LibraryError::InvalidIndex)
+    }
+
+    /// Changes the name, category and tags of a user preset.
+    ///
+    /// The file is renamed to match the new name. Returns the new index of the entry.
+    pub fn rename(&mut self, index: usize, info: PresetInfo) -> Result<usize, LibraryError> {
+        let path = self.user_path(index)?;
+        if info.name.trim().is_empty() {
+            return Err(LibraryError::EmptyName);
+        }
+
+        let data = std::fs::read(&path)?;
+        let mut file: PresetFile<serde_json::Value> = serde_json::from_slice(&data)?;
+        let renamed = file.info.name != info.name;
+        file.info = info;
+
+        let new_path = match path.parent() {
+            Some(dir) if renamed => unused_path(dir, &file.info.name),
+            _ => path.clone(),
+        };
+        write_file(&new_path, &file)?;
+        if new_path != path {
+            std::fs::remove_file(&path)?;
+        }
+
+        self.refresh();
+        self.position(&PresetSource::User(new_path))
+            .ok_or(LibraryError::InvalidIndex)
+    }
+
+    /// Deletes the file of a user preset.
+    pub fn delete(&mut self, index: usize) -> Result<(), LibraryError> {
+        let path = self.user_path(index)?;
+        std::fs::remove_file(path)?;
+        self.refresh();
+
+        Ok(())
+    }
+
+    /// Returns the path of a user preset.
+    fn user_path(&self, index: usize) -> Result<PathBuf, LibraryError> {
+        match self.entries.get(index).map(|entry| &entry.source) {
+            Some(PresetSource::User(path)) => Ok(path.clone()),
+            Some(PresetSource::Factory(_)) => Err(LibraryError::ReadOnly),
+            None => Err(LibraryError::InvalidIndex),
+        }
+    }
+}
+
+/// Reads the information of a preset file without its pattern.
+fn read_info(path: &Path) -> Result<PresetInfo, LibraryError> {
+    let data = std::fs::read(path)?;
+    let file: PresetFile<serde::de::IgnoredAny> = serde_json::from_slice(&data)?;
+
+    Ok(file.info)
+}
+
+/// Writes a preset file.
+fn write_file<P: Serialize>(path: &Path, file: &PresetFile<P>) -> Result<(), LibraryError> {
+    let data = serde_json::to_vec_pretty(file)?;
+    std::fs::write(path, data)?;
+
+    Ok(())
+}
+
+/// Returns a path for a new preset file in `dir` that is derived from the preset name.
+///
+/// Characters that are not allowed in file names on all systems are replaced, a number is
+/// appended if a file with the name already exists.
+fn unused_path(dir: &Path, name: &str) -> PathBuf {
+    let stem: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    (1..)
+        .map(|n| match n {
+            1 => dir.join(format!("{stem}.{PRESET_EXTENSION}")),
+            n => dir.join(format!("{stem} {n}.{PRESET_EXTENSION}")),
+        })
+        .find(|path| !path.exists())
+        .unwrap_or_default()
+}
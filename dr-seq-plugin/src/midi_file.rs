@@ -0,0 +1,698 @@
+//! Standard MIDI File export and import.
+
+use std::sync::atomic::Ordering;
+
+use nice_plug::prelude::*;
+
+use dr_seq_engine::{Render, RenderSettings, RenderedEvent, Sequencer, SequencerEvent, Velocity};
+
+use crate::config::{CLOCK_PPQ, EXPORT_PATTERN_BARS, NAME, REPEAT_PRIORITY, STEPS, TRACKS};
+use crate::midi_note;
+use crate::params::{AppParams, MAX_NUDGE, NoteMap, Pattern, StepState};
+
+/// Layout of a Standard MIDI File.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SmfFormat {
+    /// Type 0, all events in a single track.
+    SingleTrack,
+
+    /// Type 1, a separate track for each sequencer track.
+    #[default]
+    MultiTrack,
+}
+
+/// Content to export.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ExportSource {
+    /// The pattern that is playing.
+    #[default]
+    Pattern,
+
+    /// The chain of patterns used in song mode.
+    Chain,
+}
+
+/// Renders the pattern or chain and returns it as Standard MIDI File.
+/// - `params`: Parameters of the plugin.
+/// - `source`: Content to export.
+/// - `format`: Layout of the file.
+///
+/// The file uses the clock resolution of the engine, so the swing, track delays and
+/// manglers are preserved exactly.
+pub fn export(params: &AppParams, source: ExportSource, format: SmfFormat) -> Vec<u8> {
+    let chain = params.chain.to_chain();
+    let source = match source {
+        ExportSource::Chain if chain.bars() > 0 => ExportSource::Chain,
+        _ => ExportSource::Pattern,
+    };
+
+    let settings = RenderSettings {
+        tempo: params.tempo.load(Ordering::Relaxed),
+        bars: match source {
+            ExportSource::Pattern => EXPORT_PATTERN_BARS,
+            ExportSource::Chain => chain.bars(),
+        },
+        ppq: CLOCK_PPQ,
+        seed: params.seed.value() as u32,
+        ..Default::default()
+    };
+    let sequencer_params = params.sequencer_params();
+
+    let mut sequencer = Sequencer::new();
+    sequencer.set_repeat_priority(&REPEAT_PRIORITY);
+    params.pattern.update_tracks(
+        sequencer.patterns_mut(),
+        params.scale(),
+        params.root.value(),
+    );
+
+    let events: Vec<RenderedEvent> = match source {
+        ExportSource::Pattern => Render::new(sequencer, &sequencer_params, &settings).collect(),
+        ExportSource::Chain => {
+            // The pattern that is playing may have been edited since it was stored.
+            let active = params.bank.active.load(Ordering::Relaxed) as usize;
+            let pattern_sets: Vec<_> = params
+                .bank
+                .patterns
+                .iter()
+                .enumerate()
+                .map(|(n, pattern)| {
+                    let pattern: &Pattern = if n == active {
+                        &params.pattern
+                    } else {
+                        pattern
+                    };
+                    let mut patterns = core::array::from_fn(|_| dr_seq_engine::Pattern::new());
+                    pattern.update_tracks(&mut patterns, params.scale(), params.root.value());
+                    patterns
+                })
+                .collect();
+
+            Render::new(sequencer, &sequencer_params, &settings)
+                .with_chain(&chain, &pattern_sets)
+                .collect()
+        }
+    };
+
+    let tempo = settings.tempo;
+    match format {
+        SmfFormat::SingleTrack => {
+            let mut track = conductor_track(tempo);
+            track.events(events.iter(), &params.note_map);
+            write_file(0, &[track.finish()])
+        }
+        SmfFormat::MultiTrack => {
+            let mut tracks = vec![conductor_track(tempo).finish()];
+            for n in 0..params.track_setup.count() {
+                let mut track = TrackChunk::default();
+                track.meta(0, 0x03, params.track_setup.name(n).as_bytes());
+                track.events(
+                    events.iter().filter(|event| event_track(event) == n),
+                    &params.note_map,
+                );
+                tracks.push(track.finish());
+            }
+            write_file(1, &tracks)
+        }
+    }
+}
+
+/// Returns the number of the track of an event.
+fn event_track(event: &RenderedEvent) -> usize {
+    match event.event {
+        SequencerEvent::NoteOn { track, .. } => track,
+        SequencerEvent::NoteOff { track, .. } => track,
+        _ => usize::MAX,
+    }
+}
+
+/// Returns a track with the name, tempo and time signature.
+fn conductor_track(tempo: f64) -> TrackChunk {
+    let mut track = TrackChunk::default();
+    track.meta(0, 0x03, NAME.as_bytes());
+
+    // Tempo in microseconds per quarter note.
+    let tempo = (60_000_000.0 / tempo.max(1.0)).round() as u32;
+    track.meta(0, 0x51, &tempo.to_be_bytes()[1..]);
+
+    // 4/4 with 24 MIDI clocks per click and 8 32th notes per quarter note.
+    track.meta(0, 0x58, &[4, 2, 24, 8]);
+
+    track
+}
+
+/// Returns the complete file.
+/// - `format`: Type of the file.
+/// - `tracks`: Encoded track chunks.
+fn write_file(format: u16, tracks: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = Vec::new();
+
+    data.extend_from_slice(b"MThd");
+    data.extend_from_slice(&6u32.to_be_bytes());
+    data.extend_from_slice(&format.to_be_bytes());
+    data.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+    data.extend_from_slice(&(CLOCK_PPQ as u16).to_be_bytes());
+
+    for track in tracks {
+        data.extend_from_slice(b"MTrk");
+        data.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        data.extend_from_slice(track);
+    }
+
+    data
+}
+
+/// Track chunk being encoded.
+#[derive(Debug, Default)]
+struct TrackChunk {
+    /// Encoded events.
+    data: Vec<u8>,
+
+    /// Time of the last event in ticks.
+    time: u32,
+}
+
+impl TrackChunk {
+    /// Appends the delta time to an event at `time`.
+    fn delta(&mut self, time: u32) {
+        let delta = time.saturating_sub(self.time);
+        self.time = self.time.max(time);
+        write_variable_length(&mut self.data, delta);
+    }
+
+    /// Appends a meta event.
+    fn meta(&mut self, time: u32, kind: u8, data: &[u8]) {
+        self.delta(time);
+        self.data.extend_from_slice(&[0xFF, kind]);
+        write_variable_length(&mut self.data, data.len() as u32);
+        self.data.extend_from_slice(data);
+    }
+
+    /// Appends the note events of the sequencer.
+    /// - `events`: Events to append.
+    /// - `note_map`: Notes and channels of the tracks.
+    fn events<'a>(&mut self, events: impl Iterator<Item = &'a RenderedEvent>, note_map: &NoteMap) {
+        for event in events {
+            let (status, track, note, velocity) = match event.event {
+                SequencerEvent::NoteOn {
+                    track,
+                    pitch,
+                    velocity,
+                } => {
+                    // A note on with a velocity of 0 would be a note off.
+                    let velocity = (velocity * 127.0).round().clamp(1.0, 127.0) as u8;
+                    (
+                        0x90,
+                        track,
+                        midi_note(pitch, note_map.note(track)),
+                        velocity,
+                    )
+                }
+                SequencerEvent::NoteOff { track, pitch } => {
+                    (0x80, track, midi_note(pitch, note_map.note(track)), 0)
+                }
+                _ => continue,
+            };
+            let status = status | note_map.channel(track);
+
+            self.delta(event.pulse_no);
+            self.data.extend_from_slice(&[status, note, velocity]);
+        }
+    }
+
+    /// Appends the end of track and returns the encoded events.
+    fn finish(mut self) -> Vec<u8> {
+        let time = self.time;
+        self.meta(time, 0x2F, &[]);
+        self.data
+    }
+}
+
+/// Appends a value as variable-length quantity.
+fn write_variable_length(data: &mut Vec<u8>, value: u32) {
+    let mut bytes = [0; 5];
+    let mut count = 0;
+    let mut value = value;
+
+    loop {
+        bytes[count] = (value & 0x7F) as u8;
+        count += 1;
+        value >>= 7;
+        if value == 0 {
+            break;
+        }
+    }
+
+    // The most significant group comes first, all but the last byte have bit 7 set.
+    for (n, byte) in bytes[..count].iter().enumerate().rev() {
+        data.push(byte | if n > 0 { 0x80 } else { 0 });
+    }
+}
+
+/// Mapping of the note velocities on import.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VelocityMapping {
+    /// Use the step state with the closest velocity setting.
+    #[default]
+    States,
+
+    /// Use the exact velocities as individual step velocities.
+    Exact,
+}
+
+/// Error reading a Standard MIDI File.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmfError {
+    /// The data does not start with a header chunk.
+    MissingHeader,
+
+    /// The time division is based on SMPTE frames instead of quarter notes.
+    UnsupportedDivision,
+
+    /// The data ends within a chunk or an event.
+    UnexpectedEnd,
+
+    /// An event has no valid status byte.
+    InvalidEvent {
+        /// Position in the data.
+        offset: usize,
+    },
+}
+
+impl std::fmt::Display for SmfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingHeader => write!(f, "not a Standard MIDI File"),
+            Self::UnsupportedDivision => write!(f, "SMPTE time division is not supported"),
+            Self::UnexpectedEnd => write!(f, "unexpected end of file"),
+            Self::InvalidEvent { offset } => write!(f, "invalid event at byte {offset}"),
+        }
+    }
+}
+
+impl std::error::Error for SmfError {}
+
+/// Note of the file that was not imported as it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportIssue {
+    /// No track plays the note number, the note was skipped.
+    UnmappedNote {
+        /// Position in ticks.
+        tick: u32,
+
+        /// Note number.
+        note: u8,
+    },
+
+    /// The note is beyond the last step of the pattern, the note was skipped.
+    OutsidePattern {
+        /// Position in ticks.
+        tick: u32,
+
+        /// Note number.
+        note: u8,
+    },
+
+    /// Another note was already imported on the step, the note was skipped.
+    Duplicate {
+        /// Position in ticks.
+        tick: u32,
+
+        /// Note number.
+        note: u8,
+    },
+
+    /// The note is not on the grid of the steps, the offset is kept as nudge.
+    OffGrid {
+        /// Position in ticks.
+        tick: u32,
+
+        /// Note number.
+        note: u8,
+
+        /// Offset to the step in clock pulses.
+        nudge: i32,
+    },
+}
+
+impl std::fmt::Display for ImportIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnmappedNote { tick, note } => {
+                write!(f, "note {note} at tick {tick}: no track with this note")
+            }
+            Self::OutsidePattern { tick, note } => {
+                write!(f, "note {note} at tick {tick}: beyond the last step")
+            }
+            Self::Duplicate { tick, note } => {
+                write!(f, "note {note} at tick {tick}: step already set")
+            }
+            Self::OffGrid { tick, note, nudge } => {
+                write!(f, "note {note} at tick {tick}: off grid by {nudge} pulses")
+            }
+        }
+    }
+}
+
+/// Result of an import.
+#[derive(Debug, Default, Clone)]
+pub struct ImportReport {
+    /// Number of imported notes.
+    pub imported: usize,
+
+    /// Notes that were skipped or changed.
+    pub issues: Vec<ImportIssue>,
+}
+
+impl ImportReport {
+    /// Returns the number of skipped notes.
+    pub fn skipped(&self) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| !matches!(issue, ImportIssue::OffGrid { .. }))
+            .count()
+    }
+
+    /// Returns the number of notes that are not on the grid.
+    pub fn off_grid(&self) -> usize {
+        self.issues.len() - self.skipped()
+    }
+}
+
+impl std::fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} imported, {} skipped, {} off grid",
+            self.imported,
+            self.skipped(),
+            self.off_grid()
+        )
+    }
+}
+
+/// Replaces the pattern that is playing with the notes of a Standard MIDI File.
+/// - `data`: Content of the file.
+/// - `params`: Parameters of the plugin.
+/// - `velocities`: Mapping of the note velocities.
+///
+/// The notes are assigned to the tracks by their note numbers and placed on the closest
+/// step of the first bar, notes that can't be placed are reported.
+pub fn import(
+    data: &[u8],
+    params: &AppParams,
+    velocities: VelocityMapping,
+) -> Result<ImportReport, SmfError> {
+    let (division, mut notes) = read_notes(data)?;
+    notes.sort_by_key(|note| note.tick);
+
+    // Velocities of the step states for comparison with the note velocities.
+    let velocity_params = params.sequencer_params().velocities;
+    let state_velocities = [
+        (StepState::Normal, Velocity::Default),
+        (StepState::Accent, Velocity::Accent),
+        (StepState::Weak, Velocity::Weak),
+        (StepState::Ghost, Velocity::Ghost),
+    ]
+    .map(|(state, vel)| (state, velocity_params.velocity(vel, false) * 127.0));
+
+    let step_ticks = division as f64 / 4.0;
+    let track_notes = params.note_map.notes();
+    // Notes are only assigned to the tracks in use.
+    let used_notes = &track_notes[..params.track_setup.count()];
+    let mut report = ImportReport::default();
+    let mut occupied = [[false; STEPS]; TRACKS];
+
+    params.pattern.clear();
+
+    for MidiNote {
+        tick,
+        note,
+        velocity,
+    } in notes
+    {
+        let Some(track) = used_notes.iter().position(|n| *n == note) else {
+            report.issues.push(ImportIssue::UnmappedNote { tick, note });
+            continue;
+        };
+
+        let step = (tick as f64 / step_ticks).round() as usize;
+        if step >= STEPS {
+            report
+                .issues
+                .push(ImportIssue::OutsidePattern { tick, note });
+            continue;
+        }
+
+        if occupied[track][step] {
+            report.issues.push(ImportIssue::Duplicate { tick, note });
+            continue;
+        }
+        occupied[track][step] = true;
+
+        let offset = tick as f64 - step as f64 * step_ticks;
+        let nudge = (offset * CLOCK_PPQ as f64 / division as f64).round() as i32;
+        if nudge != 0 {
+            report
+                .issues
+                .push(ImportIssue::OffGrid { tick, note, nudge });
+        }
+
+        let state = match velocities {
+            VelocityMapping::States => state_velocities
+                .iter()
+                .min_by(|a, b| {
+                    let a = (a.1 - velocity as f32).abs();
+                    let b = (b.1 - velocity as f32).abs();
+                    a.total_cmp(&b)
+                })
+                .map(|(state, _)| *state)
+                .unwrap_or_default(),
+            VelocityMapping::Exact => StepState::Custom,
+        };
+
+        let pattern = &params.pattern;
+        pattern.steps[track][step].store(state.into(), Ordering::Relaxed);
+        pattern.velocities[track][step].store(velocity as u32, Ordering::Relaxed);
+        pattern.nudges[track][step].store(nudge.clamp(-MAX_NUDGE, MAX_NUDGE), Ordering::Relaxed);
+
+        report.imported += 1;
+    }
+
+    Ok(report)
+}
+
+/// Note on event read from a file.
+#[derive(Debug, Clone, Copy)]
+struct MidiNote {
+    /// Position in ticks.
+    tick: u32,
+
+    /// Note number.
+    note: u8,
+
+    /// Velocity in the range of 1-127.
+    velocity: u8,
+}
+
+/// Returns the ticks per quarter note and the note ons of all tracks.
+fn read_notes(data: &[u8]) -> Result<(u16, Vec<MidiNote>), SmfError> {
+    let mut reader = Reader {
+        data,
+        base: 0,
+        offset: 0,
+    };
+
+    if reader.bytes(4) != Ok(b"MThd") {
+        return Err(SmfError::MissingHeader);
+    }
+    let header_length = reader.u32()? as usize;
+    let _format = reader.u16()?;
+    let _tracks = reader.u16()?;
+    let division = reader.u16()?;
+    reader.bytes(header_length.saturating_sub(6))?;
+
+    if division & 0x8000 != 0 || division == 0 {
+        return Err(SmfError::UnsupportedDivision);
+    }
+
+    let mut notes = Vec::new();
+
+    while reader.offset < data.len() {
+        let id = reader.bytes(4)?;
+        let length = reader.u32()? as usize;
+        let chunk = reader.bytes(length)?;
+
+        // Unknown chunks must be ignored.
+        if id == b"MTrk" {
+            read_track(chunk, reader.offset - length, &mut notes)?;
+        }
+    }
+
+    Ok((division, notes))
+}
+
+/// Reads the note ons of a track chunk.
+/// - `data`: Content of the chunk.
+/// - `base`: Position of the chunk in the file, used for error reporting.
+/// - `notes`: Vector the notes are appended to.
+fn read_track(data: &[u8], base: usize, notes: &mut Vec<MidiNote>) -> Result<(), SmfError> {
+    let mut reader = Reader {
+        data,
+        base,
+        offset: 0,
+    };
+    let mut tick = 0u32;
+    let mut running_status = None;
+
+    while reader.offset < data.len() {
+        tick = tick.saturating_add(reader.variable_length()?);
+
+        let offset = reader.offset;
+        let first = data.get(offset).copied().ok_or(SmfError::UnexpectedEnd)?;
+        let status = if first & 0x80 != 0 {
+            reader.offset += 1;
+            first
+        } else {
+            running_status.ok_or(SmfError::InvalidEvent {
+                offset: base + offset,
+            })?
+        };
+
+        match status {
+            0xFF => {
+                // Meta event.
+                reader.bytes(1)?;
+                let length = reader.variable_length()? as usize;
+                reader.bytes(length)?;
+                running_status = None;
+            }
+            0xF0 | 0xF7 => {
+                // System exclusive event.
+                let length = reader.variable_length()? as usize;
+                reader.bytes(length)?;
+                running_status = None;
+            }
+            0x80..=0xEF => {
+                running_status = Some(status);
+                let length = if matches!(status & 0xF0, 0xC0 | 0xD0) {
+                    1
+                } else {
+                    2
+                };
+                let bytes = reader.bytes(length)?;
+                if status & 0xF0 == 0x90 && bytes[1] > 0 {
+                    notes.push(MidiNote {
+                        tick,
+                        note: bytes[0] & 0x7F,
+                        velocity: bytes[1] & 0x7F,
+                    });
+                }
+            }
+            _ => {
+                return Err(SmfError::InvalidEvent {
+                    offset: base + offset,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reader for big-endian values.
+struct Reader<'a> {
+    /// Data to read from.
+    data: &'a [u8],
+
+    /// Position of the data in the file, used for error reporting.
+    base: usize,
+
+    /// Position of the next byte.
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Returns the next `count` bytes.
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], SmfError> {
+        let end = self
+            .offset
+            .checked_add(count)
+            .ok_or(SmfError::UnexpectedEnd)?;
+        let bytes = self
+            .data
+            .get(self.offset..end)
+            .ok_or(SmfError::UnexpectedEnd)?;
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    /// Returns the next 16-bit value.
+    fn u16(&mut self) -> Result<u16, SmfError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Returns the next 32-bit value.
+    fn u32(&mut self) -> Result<u32, SmfError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Returns the next variable-length quantity.
+    fn variable_length(&mut self) -> Result<u32, SmfError> {
+        let start = self.offset;
+        let mut value = 0u32;
+
+        // The value is limited to 4 bytes.
+        for _ in 0..4 {
+            let byte = self.bytes(1)?[0];
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(SmfError::InvalidEvent {
+            offset: self.base + start,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncated_track_is_an_error() {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"MThd");
+        data.extend_from_slice(&6u32.to_be_bytes());
+        data.extend_from_slice(&[0, 0, 0, 1, 0, 96]);
+
+        // The track ends after the delta time of an event.
+        data.extend_from_slice(b"MTrk");
+        data.extend_from_slice(&1u32.to_be_bytes());
+        data.push(0x00);
+
+        assert!(matches!(read_notes(&data), Err(SmfError::UnexpectedEnd)));
+    }
+
+    #[test]
+    fn invalid_delta_time_reports_file_offset() {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"MThd");
+        data.extend_from_slice(&6u32.to_be_bytes());
+        data.extend_from_slice(&[0, 0, 0, 1, 0, 96]);
+
+        // The delta time of the first event is longer than 4 bytes.
+        data.extend_from_slice(b"MTrk");
+        data.extend_from_slice(&5u32.to_be_bytes());
+        data.extend_from_slice(&[0x80, 0x80, 0x80, 0x80, 0x00]);
+
+        assert!(matches!(
+            read_notes(&data),
+            Err(SmfError::InvalidEvent { offset: 22 })
+        ));
+    }
+}
@@ -1,15 +1,26 @@
 //! Plugin parameters.
 
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
 
+use atomic_float::AtomicF64;
 use nice_plug::params::persist::PersistentField;
 use nice_plug::prelude::*;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use vizia_plug::ViziaState;
 
-use crate::config::{CLOCK_PPQ, TRACKS};
+use dr_seq_engine::{
+    Chain, ChainEntry, Condition, Euclid, Gate, MAX_CHAIN_ENTRIES, MAX_STEP_EVENTS, Manglers,
+    Pitch, Ramp, Scale, SequencerParams, StepEvent, TrackSettings, Velocity, VelocityParams,
+};
+
+use crate::config::{
+    ACCENT_TRACK, BANK_SIZE, CHOKE_GROUPS, CLOCK_PPQ, DEFAULT_NOTE_TRACKS, MAX_NOTE_TRACKS,
+    MIN_NOTE_TRACKS, TRACK_LABELS, TRACK_NOTES, TRACKS,
+};
 use crate::editor;
+use crate::state::{Migrate, Migration, Versioned, keep_layout};
 
 #[derive(Params)]
 pub struct AppParams {
@@ -19,82 +30,79 @@ pub struct AppParams {
 
     /// State of the pattern in the grid.
     #[persist = "pattern"]
-    pub pattern: Pattern,
+    pub pattern: Versioned<Pattern>,
 
-    /// Number of the current step.
-    pub current_step: AtomicUsize,
+    /// Bank of stored patterns.
+    #[persist = "bank"]
+    pub bank: Versioned<PatternBank>,
 
-    /// Swing.
-    #[id = "swing"]
-    pub swing: IntParam,
+    /// Chain of patterns for song mode.
+    #[persist = "chain"]
+    pub chain: Versioned<ChainState>,
 
-    /// Triplet mode.
-    #[id = "triplet"]
-    pub triplet: BoolParam,
+    /// Number, names, choke groups and accent lanes of the note tracks.
+    #[persist = "tracks"]
+    pub track_setup: Versioned<TrackSetup>,
 
-    /// Track 1 enable.
-    #[id = "track1-enable"]
-    pub track1_enable: BoolParam,
+    /// Notes and MIDI channels used by the tracks.
+    #[persist = "note-map"]
+    pub note_map: Versioned<NoteMap>,
 
-    /// Track 2 enable.
-    #[id = "track2-enable"]
-    pub track2_enable: BoolParam,
+    /// Counter incremented when the pattern is changed outside of the editor,
+    /// e.g. by a pattern switch.
+    pub pattern_version: Arc<AtomicU32>,
 
-    /// Track 3 enable.
-    #[id = "track3-enable"]
-    pub track3_enable: BoolParam,
+    /// Number of the current step.
+    pub current_step: AtomicUsize,
 
-    /// Track 4 enable.
-    #[id = "track4-enable"]
-    pub track4_enable: BoolParam,
+    /// Tempo of the host in beats per minute.
+    pub tempo: AtomicF64,
 
-    /// Track 5 enable.
-    #[id = "track5-enable"]
-    pub track5_enable: BoolParam,
+    /// Number of the track selected in the editor for the lanes.
+    pub selected_track: Arc<AtomicU32>,
 
-    /// Track 6 enable.
-    #[id = "track6-enable"]
-    pub track6_enable: BoolParam,
+    /// Lane shown in the editor for the selected track.
+    pub selected_lane: Arc<AtomicU32>,
 
-    /// Track 7 enable.
-    #[id = "track7-enable"]
-    pub track7_enable: BoolParam,
+    /// Swing.
+    #[id = "swing"]
+    pub swing: IntParam,
 
-    /// Track 8 enable.
-    #[id = "track8-enable"]
-    pub track8_enable: BoolParam,
+    /// Triplet mode.
+    #[id = "triplet"]
+    pub triplet: BoolParam,
 
-    /// Track 1 delay.
-    #[id = "track1-delay"]
-    pub track1_delay: IntParam,
+    /// Fill mode for trigger conditions.
+    #[id = "fill"]
+    pub fill: BoolParam,
 
-    /// Track 2 delay.
-    #[id = "track2-delay"]
-    pub track2_delay: IntParam,
+    /// Pattern selected for playback, switched at the end of the bar or pattern.
+    #[id = "pattern-select"]
+    pub pattern_select: IntParam,
 
-    /// Track 3 delay.
-    #[id = "track3-delay"]
-    pub track3_delay: IntParam,
+    /// Switch patterns at the end of the pattern instead of the bar.
+    #[id = "switch-mode"]
+    pub switch_mode: BoolParam,
 
-    /// Track 4 delay.
-    #[id = "track4-delay"]
-    pub track4_delay: IntParam,
+    /// Song mode, playing the patterns of the chain.
+    #[id = "song-mode"]
+    pub song_mode: BoolParam,
 
-    /// Track 5 delay.
-    #[id = "track5-delay"]
-    pub track5_delay: IntParam,
+    /// Seed for the step probabilities.
+    #[id = "seed"]
+    pub seed: IntParam,
 
-    /// Track 6 delay.
-    #[id = "track6-delay"]
-    pub track6_delay: IntParam,
+    /// Scale for quantizing the notes of melodic tracks.
+    #[id = "scale"]
+    pub scale: IntParam,
 
-    /// Track 7 delay.
-    #[id = "track7-delay"]
-    pub track7_delay: IntParam,
+    /// Root note of the scale.
+    #[id = "root"]
+    pub root: IntParam,
 
-    /// Track 8 delay.
-    #[id = "track8-delay"]
-    pub track8_delay: IntParam,
+    /// Settings of the note tracks.
+    #[nested]
+    pub tracks: TrackParamsArray,
 
     /// Normal velocity for steps.
     #[id = "normal-velocity"]
@@ -148,15 +156,18 @@ pub struct AppParams {
 impl AppParams {
     /// Returns a new instance.
     pub fn new(update_engine: Arc<AtomicBool>) -> Self {
-        let delay_range = IntRange::Linear {
-            min: -(CLOCK_PPQ as i32) / 8,
-            max: (CLOCK_PPQ as i32) / 8,
-        };
-
         Self {
             editor_state: editor::default_state(),
-            pattern: Pattern::default(),
+            pattern: Versioned::default(),
+            bank: Versioned::default(),
+            chain: Versioned::default(),
+            track_setup: Versioned::default(),
+            note_map: Versioned::default(),
+            pattern_version: Arc::new(AtomicU32::new(0)),
             current_step: AtomicUsize::new(0),
+            tempo: AtomicF64::new(120.0),
+            selected_track: Arc::new(AtomicU32::new(0)),
+            selected_lane: Arc::new(AtomicU32::new(0)),
             swing: IntParam::new("Swing", 0, IntRange::Linear { min: 0, max: 100 }).with_callback(
                 {
                     let update_engine = update_engine.clone();
@@ -164,111 +175,66 @@ impl AppParams {
                 },
             ),
             triplet: BoolParam::new("3/4", false),
-
-            // Track enables
-            track1_enable: BoolParam::new("Track 1 Enable", true)
-                .with_callback({
-                    let update_engine = update_engine.clone();
-                    Arc::new(move |_| update_engine.store(true, Ordering::Release))
-                })
-                .with_value_to_string(Arc::new(|value| {
-                    String::from(if value { "on" } else { "off" })
-                })),
-            track2_enable: BoolParam::new("Track 2 Enable", true)
-                .with_callback({
-                    let update_engine = update_engine.clone();
-                    Arc::new(move |_| update_engine.store(true, Ordering::Release))
-                })
-                .with_value_to_string(Arc::new(|value| {
-                    String::from(if value { "on" } else { "off" })
-                })),
-            track3_enable: BoolParam::new("Track 3 Enable", true)
-                .with_callback({
-                    let update_engine = update_engine.clone();
-                    Arc::new(move |_| update_engine.store(true, Ordering::Release))
-                })
-                .with_value_to_string(Arc::new(|value| {
-                    String::from(if value { "on" } else { "off" })
-                })),
-            track4_enable: BoolParam::new("Track 4 Enable", true)
-                .with_callback({
-                    let update_engine = update_engine.clone();
-                    Arc::new(move |_| update_engine.store(true, Ordering::Release))
-                })
-                .with_value_to_string(Arc::new(|value| {
-                    String::from(if value { "on" } else { "off" })
-                })),
-            track5_enable: BoolParam::new("Track 5 Enable", true)
-                .with_callback({
-                    let update_engine = update_engine.clone();
-                    Arc::new(move |_| update_engine.store(true, Ordering::Release))
-                })
-                .with_value_to_string(Arc::new(|value| {
-                    String::from(if value { "on" } else { "off" })
-                })),
-            track6_enable: BoolParam::new("Track 6 Enable", true)
-                .with_callback({
-                    let update_engine = update_engine.clone();
-                    Arc::new(move |_| update_engine.store(true, Ordering::Release))
-                })
-                .with_value_to_string(Arc::new(|value| {
-                    String::from(if value { "on" } else { "off" })
-                })),
-            track7_enable: BoolParam::new("Track 7 Enable", true)
-                .with_callback({
-                    let update_engine = update_engine.clone();
-                    Arc::new(move |_| update_engine.store(true, Ordering::Release))
-                })
-                .with_value_to_string(Arc::new(|value| {
-                    String::from(if value { "on" } else { "off" })
-                })),
-            track8_enable: BoolParam::new("Track 8 Enable", true)
+            fill: BoolParam::new("FILL", false),
+            pattern_select: IntParam::new(
+                "Pattern",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: BANK_SIZE as i32,
+                },
+            ),
+            switch_mode: BoolParam::new("Switch Mode", false).with_value_to_string(Arc::new(
+                |value| String::from(if value { "pattern" } else { "bar" }),
+            )),
+            song_mode: BoolParam::new("SONG", false),
+            seed: IntParam::new("Seed", 0, IntRange::Linear { min: 0, max: 999 }),
+            scale: IntParam::new(
+                "Scale",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: Scale::ALL.len() as i32 - 1,
+                },
+            )
+            .with_callback({
+                let update_engine = update_engine.clone();
+                Arc::new(move |_| update_engine.store(true, Ordering::Release))
+            })
+            .with_value_to_string(Arc::new(|value| {
+                String::from(
+                    Scale::ALL[value.clamp(0, Scale::ALL.len() as i32 - 1) as usize].name(),
+                )
+            }))
+            .with_string_to_value(Arc::new(|string| {
+                Scale::ALL
+                    .iter()
+                    .position(|scale| scale.name().eq_ignore_ascii_case(string.trim()))
+                    .map(|index| index as i32)
+            })),
+            root: IntParam::new("Root", 0, IntRange::Linear { min: 0, max: 11 })
                 .with_callback({
                     let update_engine = update_engine.clone();
                     Arc::new(move |_| update_engine.store(true, Ordering::Release))
                 })
                 .with_value_to_string(Arc::new(|value| {
-                    String::from(if value { "on" } else { "off" })
+                    String::from(NOTE_NAMES[value.rem_euclid(12) as usize])
+                }))
+                .with_string_to_value(Arc::new(|string| {
+                    NOTE_NAMES
+                        .iter()
+                        .position(|name| name.eq_ignore_ascii_case(string.trim()))
+                        .map(|index| index as i32)
                 })),
 
-            // Track delays
-            track1_delay: IntParam::new("Track 1 Delay", 0, delay_range).with_callback({
-                let update_engine = update_engine.clone();
-                Arc::new(move |_| update_engine.store(true, Ordering::Release))
-            }),
-            track2_delay: IntParam::new("Track 2 Delay", 0, delay_range).with_callback({
-                let update_engine = update_engine.clone();
-                Arc::new(move |_| update_engine.store(true, Ordering::Release))
-            }),
-            track3_delay: IntParam::new("Track 3 Delay", 0, delay_range).with_callback({
-                let update_engine = update_engine.clone();
-                Arc::new(move |_| update_engine.store(true, Ordering::Release))
-            }),
-            track4_delay: IntParam::new("Track 4 Delay", 0, delay_range).with_callback({
-                let update_engine = update_engine.clone();
-                Arc::new(move |_| update_engine.store(true, Ordering::Release))
-            }),
-            track5_delay: IntParam::new("Track 5 Delay", 0, delay_range).with_callback({
-                let update_engine = update_engine.clone();
-                Arc::new(move |_| update_engine.store(true, Ordering::Release))
-            }),
-            track6_delay: IntParam::new("Track 6 Delay", 0, delay_range).with_callback({
-                let update_engine = update_engine.clone();
-                Arc::new(move |_| update_engine.store(true, Ordering::Release))
-            }),
-            track7_delay: IntParam::new("Track 7 Delay", 0, delay_range).with_callback({
-                let update_engine = update_engine.clone();
-                Arc::new(move |_| update_engine.store(true, Ordering::Release))
-            }),
-            track8_delay: IntParam::new("Track 8 Delay", 0, delay_range).with_callback(Arc::new(
-                move |_| update_engine.store(true, Ordering::Release),
-            )),
+            // Note tracks
+            tracks: TrackParamsArray::new(update_engine),
 
             // Velocities
             normal_velocity: IntParam::new("Velocity", 100, IntRange::Linear { min: 0, max: 127 }),
             accent_velocity: IntParam::new(
                 "Accent Velocity",
-                27,
+                DEFAULT_ACCENT_VELOCITY as i32,
                 IntRange::Linear { min: 0, max: 127 },
             ),
             accent_vel_mode: BoolParam::new("Accent Vel Mode", false).with_value_to_string(
@@ -295,6 +261,271 @@ impl AppParams {
             mangler_slow: BoolParam::new("SLOW", false),
         }
     }
+
+    /// Returns the scale selected for melodic tracks.
+    pub fn scale(&self) -> Scale {
+        Scale::ALL
+            .get(self.scale.value() as usize)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the sequencer parameters from the current parameter values.
+    pub fn sequencer_params(&self) -> SequencerParams<TRACKS> {
+        let params = self;
+
+        let mut tracks = [TrackSettings::default(); TRACKS];
+        for (settings, track_params) in tracks.iter_mut().zip(params.tracks.iter()) {
+            settings.enable = track_params.enable.value();
+            settings.delay = track_params.delay.value();
+            settings.gate = track_params.gate.value() as u16;
+        }
+
+        for (n, settings) in tracks.iter_mut().enumerate() {
+            // Note tracks not in use are never played.
+            if !params.track_setup.is_active(n) {
+                settings.enable = false;
+            }
+            settings.choke_group = params.track_setup.choke_group(n);
+            settings.accent_lane = params.track_setup.accent_lane(n);
+        }
+
+        SequencerParams {
+            tracks,
+            swing: params.swing.value(),
+            triplet: params.triplet.value(),
+            fill: params.fill.value(),
+            manglers: Manglers {
+                fast: params.mangler_fast.value(),
+                slow: params.mangler_slow.value(),
+                swag: params.mangler_swag.value(),
+                mirror: params.mangler_mirror.value(),
+                hack: params.mangler_hack.value(),
+                sole: params.mangler_sole.value(),
+                flame: params.mangler_flame.value(),
+            },
+            velocities: VelocityParams {
+                normal: params.normal_velocity.value() as u8,
+                accent: params.accent_velocity.value() as u8,
+                accent_absolute: params.accent_vel_mode.value(),
+                weak: params.weak_velocity.value() as u8,
+                ghost: params.ghost_velocity.value() as u8,
+            },
+            accent_track: Some(ACCENT_TRACK as usize),
+        }
+    }
+}
+
+/// Parameters of a note track.
+pub struct TrackParams {
+    /// Enable.
+    pub enable: BoolParam,
+
+    /// Timing offset in clock pulses.
+    pub delay: IntParam,
+
+    /// Note length in percent of a step.
+    pub gate: IntParam,
+}
+
+/// Parameters of all note tracks.
+///
+/// The parameter IDs are numbered from 1, e.g. `track1-enable`, as they were before the
+/// number of tracks became configurable, so automation in existing projects is kept.
+pub struct TrackParamsArray([TrackParams; MAX_NOTE_TRACKS]);
+
+impl TrackParamsArray {
+    /// Returns a new instance.
+    /// - `update_engine`: Flag set when a value is changed.
+    fn new(update_engine: Arc<AtomicBool>) -> Self {
+        let delay_range = IntRange::Linear {
+            min: -(CLOCK_PPQ as i32) / 8,
+            max: (CLOCK_PPQ as i32) / 8,
+        };
+
+        // Gate range in percent of a step, up to a half note.
+        let gate_range = IntRange::Linear { min: 1, max: 800 };
+
+        Self(core::array::from_fn(|n| TrackParams {
+            enable: BoolParam::new(format!("Track {} Enable", n + 1), true)
+                .with_callback(engine_callback(&update_engine))
+                .with_value_to_string(Arc::new(|value| {
+                    String::from(if value { "on" } else { "off" })
+                })),
+            delay: IntParam::new(format!("Track {} Delay", n + 1), 0, delay_range)
+                .with_callback(engine_callback(&update_engine)),
+            gate: IntParam::new(format!("Track {} Gate", n + 1), 50, gate_range)
+                .with_unit(" %")
+                .with_callback(engine_callback(&update_engine)),
+        }))
+    }
+}
+
+/// Returns a parameter callback that sets the flag for an engine update.
+fn engine_callback<T>(update_engine: &Arc<AtomicBool>) -> Arc<dyn Fn(T) + Send + Sync> {
+    let update_engine = update_engine.clone();
+    Arc::new(move |_| update_engine.store(true, Ordering::Release))
+}
+
+impl std::ops::Deref for TrackParamsArray {
+    type Target = [TrackParams; MAX_NOTE_TRACKS];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+unsafe impl Params for TrackParamsArray {
+    fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+        self.0
+            .iter()
+            .enumerate()
+            .flat_map(|(n, track)| {
+                let n = n + 1;
+                [
+                    (format!("track{n}-enable"), track.enable.as_ptr()),
+                    (format!("track{n}-delay"), track.delay.as_ptr()),
+                    (format!("track{n}-gate"), track.gate.as_ptr()),
+                ]
+            })
+            .map(|(id, ptr)| (id, ptr, String::new()))
+            .collect()
+    }
+}
+
+/// Number, names, choke groups and accent lanes of the note tracks.
+#[derive(Serialize, Deserialize)]
+pub struct TrackSetup {
+    /// Number of note tracks in use.
+    #[serde(default = "default_track_count")]
+    pub count: Arc<AtomicU32>,
+
+    /// Names of the note tracks, empty for the default label.
+    #[serde(default)]
+    pub names: [RwLock<String>; MAX_NOTE_TRACKS],
+
+    /// Choke groups of the note tracks, 0 for none.
+    #[serde(default)]
+    pub choke_groups: [Arc<AtomicU32>; MAX_NOTE_TRACKS],
+
+    /// Allow only one track of a choke group to be set on a step when editing.
+    #[serde(default)]
+    pub exclusive_chokes: Arc<AtomicBool>,
+
+    /// Flags if the accent lanes of the note tracks are played.
+    #[serde(default)]
+    pub accent_lanes: [Arc<AtomicBool>; MAX_NOTE_TRACKS],
+
+    /// Accent velocities of the accent lanes in range 0-127, applied like the accent
+    /// velocity of the accent track.
+    #[serde(default = "default_accent_velocities")]
+    pub accent_velocities: [Arc<AtomicU32>; MAX_NOTE_TRACKS],
+}
+
+impl Default for TrackSetup {
+    fn default() -> Self {
+        Self {
+            count: default_track_count(),
+            names: Default::default(),
+            choke_groups: Default::default(),
+            exclusive_chokes: Default::default(),
+            accent_lanes: Default::default(),
+            accent_velocities: default_accent_velocities(),
+        }
+    }
+}
+
+impl TrackSetup {
+    /// Returns the number of note tracks in use.
+    pub fn count(&self) -> usize {
+        (self.count.load(Ordering::Relaxed) as usize).clamp(MIN_NOTE_TRACKS, MAX_NOTE_TRACKS)
+    }
+
+    /// Returns if a track is in use, which is always the case for the accent track.
+    pub fn is_active(&self, track: usize) -> bool {
+        track < self.count() || track == ACCENT_TRACK as usize
+    }
+
+    /// Returns the name of a track shown in the editor.
+    pub fn name(&self, track: usize) -> String {
+        let name = self
+            .names
+            .get(track)
+            .and_then(|name| name.read().ok().map(|name| name.clone()))
+            .unwrap_or_default();
+
+        if name.is_empty() {
+            TRACK_LABELS.get(track).copied().unwrap_or_default().into()
+        } else {
+            name
+        }
+    }
+
+    /// Returns the choke group of a track in use.
+    pub fn choke_group(&self, track: usize) -> Option<u8> {
+        match self.choke_groups.get(track) {
+            Some(group) if self.is_active(track) => {
+                let group = group.load(Ordering::Relaxed).min(CHOKE_GROUPS);
+                (group > 0).then_some(group as u8)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the accent velocity of the accent lane of a track, if the lane is played.
+    pub fn accent_lane(&self, track: usize) -> Option<u8> {
+        let playing = self.accent_lanes.get(track)?.load(Ordering::Relaxed);
+        let velocity = self.accent_velocities.get(track)?.load(Ordering::Relaxed);
+
+        playing.then_some(velocity.min(127) as u8)
+    }
+
+    /// Sets the name of a note track, an empty name restores the default label.
+    pub fn set_name(&self, track: usize, name: &str) {
+        if let Some(Ok(mut track_name)) = self.names.get(track).map(RwLock::write) {
+            *track_name = name.trim().into();
+        }
+    }
+}
+
+/// Returns the default number of note tracks.
+fn default_track_count() -> Arc<AtomicU32> {
+    Arc::new(AtomicU32::new(DEFAULT_NOTE_TRACKS as u32))
+}
+
+/// Returns the default accent velocities of the accent lanes.
+fn default_accent_velocities() -> [Arc<AtomicU32>; MAX_NOTE_TRACKS] {
+    core::array::from_fn(|_| Arc::new(AtomicU32::new(DEFAULT_ACCENT_VELOCITY)))
+}
+
+impl<'a> PersistentField<'a, TrackSetup> for TrackSetup {
+    fn set(&self, new_value: TrackSetup) {
+        self.count
+            .store(new_value.count.load(Ordering::Relaxed), Ordering::Relaxed);
+        for (track, name) in new_value.names.into_iter().enumerate() {
+            self.set_name(track, &name.into_inner().unwrap_or_default());
+        }
+        for (group, new_group) in self.choke_groups.iter().zip(new_value.choke_groups) {
+            group.store(new_group.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        self.exclusive_chokes.store(
+            new_value.exclusive_chokes.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        copy_values(&self.accent_lanes, &new_value.accent_lanes);
+        copy_values(&self.accent_velocities, &new_value.accent_velocities);
+    }
+
+    fn map<F, R>(&self, f: F) -> R
+    where
+        F: Fn(&TrackSetup) -> R,
+    {
+        f(self)
+    }
+}
+
+impl Migrate for TrackSetup {
+    const MIGRATIONS: [Migration; 2] = [keep_layout, keep_layout];
 }
 
 /// Possible states of a step.
@@ -315,6 +546,9 @@ pub enum StepState {
 
     /// Ghost note with low velocity.
     Ghost,
+
+    /// Step with an individual velocity.
+    Custom,
 }
 
 impl From<StepState> for u32 {
@@ -325,6 +559,7 @@ impl From<StepState> for u32 {
             StepState::Accent => 2,
             StepState::Weak => 3,
             StepState::Ghost => 4,
+            StepState::Custom => 5,
         }
     }
 }
@@ -336,26 +571,467 @@ impl From<u32> for StepState {
             2 => StepState::Accent,
             3 => StepState::Weak,
             4 => StepState::Ghost,
+            5 => StepState::Custom,
             _ => StepState::Off,
         }
     }
 }
 
-#[derive(Default, Serialize, Deserialize)]
+/// Modes of a track.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TrackMode {
+    /// All steps play the note of the track.
+    #[default]
+    Drum,
+
+    /// Each step plays its own note.
+    Melodic,
+}
+
+impl From<TrackMode> for u32 {
+    fn from(value: TrackMode) -> Self {
+        match value {
+            TrackMode::Drum => 0,
+            TrackMode::Melodic => 1,
+        }
+    }
+}
+
+impl From<u32> for TrackMode {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => TrackMode::Melodic,
+            _ => TrackMode::Drum,
+        }
+    }
+}
+
+/// Names of the notes within an octave.
+pub const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Trigger conditions that can be selected for a step.
+///
+/// The persisted condition value of a step is the index into this list.
+pub const STEP_CONDITIONS: [Condition; 14] = [
+    Condition::Always,
+    Condition::Ratio { pos: 1, len: 2 },
+    Condition::Ratio { pos: 2, len: 2 },
+    Condition::Ratio { pos: 1, len: 3 },
+    Condition::Ratio { pos: 2, len: 3 },
+    Condition::Ratio { pos: 3, len: 3 },
+    Condition::Ratio { pos: 1, len: 4 },
+    Condition::Ratio { pos: 2, len: 4 },
+    Condition::Ratio { pos: 3, len: 4 },
+    Condition::Ratio { pos: 4, len: 4 },
+    Condition::First,
+    Condition::NotFirst,
+    Condition::Fill,
+    Condition::NotFill,
+];
+
+/// Note lengths that can be selected for a step.
+///
+/// The persisted gate value of a step is the index into this list,
+/// `None` uses the gate setting of the track.
+pub const STEP_GATES: [Option<Gate>; 7] = [
+    None,
+    Some(Gate::Length(25)),
+    Some(Gate::Length(50)),
+    Some(Gate::Length(100)),
+    Some(Gate::Length(200)),
+    Some(Gate::Length(400)),
+    Some(Gate::Tie),
+];
+
+/// Velocity ramps that can be selected for a step.
+///
+/// The persisted ramp value of a step is the index into this list.
+pub const STEP_RAMPS: [Ramp; 3] = [Ramp::Flat, Ramp::Up, Ramp::Down];
+
+/// Ratchet counts that can be selected for a step.
+pub const STEP_RATCHETS: [u32; 6] = [1, 2, 3, 4, 6, 8];
+
+/// Maximum number of notes that can be layered on top of the note of a step.
+pub const MAX_LAYERS: usize = MAX_STEP_EVENTS - 1;
+
+/// Default accent velocity of the accent track and the accent lanes.
+const DEFAULT_ACCENT_VELOCITY: u32 = 27;
+
+/// Maximum timing offset of a step in clock pulses, which is half a step.
+pub const MAX_NUDGE: i32 = CLOCK_PPQ as i32 / 8;
+
+#[derive(Serialize, Deserialize)]
 pub struct Pattern {
     /// Array of tracks with steps.
     pub steps: [[Arc<AtomicU32>; 16]; TRACKS],
+
+    /// Active length in steps for each track.
+    #[serde(default = "default_lengths")]
+    pub lengths: [Arc<AtomicU32>; TRACKS],
+
+    /// Trigger conditions of the steps as index into `STEP_CONDITIONS`.
+    #[serde(default)]
+    pub conditions: [[Arc<AtomicU32>; 16]; TRACKS],
+
+    /// Trigger probabilities of the steps in percent.
+    #[serde(default = "default_probabilities")]
+    pub probabilities: [[Arc<AtomicU32>; 16]; TRACKS],
+
+    /// Note lengths of the steps as index into `STEP_GATES`.
+    #[serde(default)]
+    pub gates: [[Arc<AtomicU32>; 16]; TRACKS],
+
+    /// Timing offsets of the steps in clock pulses.
+    #[serde(default)]
+    pub nudges: [[Arc<AtomicI32>; 16]; TRACKS],
+
+    /// Number of hits of the steps.
+    #[serde(default = "default_ratchets")]
+    pub ratchets: [[Arc<AtomicU32>; 16]; TRACKS],
+
+    /// Velocity ramps of the ratchets as index into `STEP_RAMPS`.
+    #[serde(default)]
+    pub ramps: [[Arc<AtomicU32>; 16]; TRACKS],
+
+    /// Individual velocities of the steps in range 0-127, used by steps in `Custom` state.
+    #[serde(default = "default_velocities")]
+    pub velocities: [[Arc<AtomicU32>; 16]; TRACKS],
+
+    /// Modes of the tracks as value of `TrackMode`.
+    #[serde(default)]
+    pub modes: [Arc<AtomicU32>; TRACKS],
+
+    /// Note numbers of the steps, used by tracks in `Melodic` mode.
+    #[serde(default = "default_notes")]
+    pub notes: [[Arc<AtomicU32>; 16]; TRACKS],
+
+    /// Layered notes of the steps as bitmask, bit n selects the n-th layer note of the track.
+    #[serde(default)]
+    pub layers: [[Arc<AtomicU32>; 16]; TRACKS],
+
+    /// Note numbers that can be layered on the steps of each track.
+    #[serde(default = "default_layer_notes")]
+    pub layer_notes: [[Arc<AtomicU32>; MAX_LAYERS]; TRACKS],
+
+    /// Flags if the euclidean rhythms of the tracks are played instead of the steps.
+    #[serde(default)]
+    pub euclid_enabled: [Arc<AtomicBool>; TRACKS],
+
+    /// Number of hits of the euclidean rhythms.
+    #[serde(default = "default_euclid_pulses")]
+    pub euclid_pulses: [Arc<AtomicU32>; TRACKS],
+
+    /// Length in steps of the euclidean rhythms.
+    #[serde(default = "default_lengths")]
+    pub euclid_steps: [Arc<AtomicU32>; TRACKS],
+
+    /// Rotation in steps of the euclidean rhythms.
+    #[serde(default)]
+    pub euclid_rotations: [Arc<AtomicU32>; TRACKS],
+
+    /// Flags if the steps are set on the accent lane of their track.
+    #[serde(default)]
+    pub accents: [[Arc<AtomicBool>; 16]; TRACKS],
 }
 
-impl<'a> PersistentField<'a, Pattern> for Pattern {
-    fn set(&self, new_value: Pattern) {
-        for (track, new_track) in self.steps.iter().zip(new_value.steps) {
-            for (step, new_step) in track.iter().zip(new_track) {
-                step.store(new_step.load(Ordering::Relaxed), Ordering::Relaxed)
+impl Default for Pattern {
+    fn default() -> Self {
+        Self {
+            steps: Default::default(),
+            lengths: default_lengths(),
+            conditions: Default::default(),
+            probabilities: default_probabilities(),
+            gates: Default::default(),
+            nudges: Default::default(),
+            ratchets: default_ratchets(),
+            ramps: Default::default(),
+            velocities: default_velocities(),
+            modes: Default::default(),
+            notes: default_notes(),
+            layers: Default::default(),
+            layer_notes: default_layer_notes(),
+            euclid_enabled: Default::default(),
+            euclid_pulses: default_euclid_pulses(),
+            euclid_steps: default_lengths(),
+            euclid_rotations: Default::default(),
+            accents: Default::default(),
+        }
+    }
+}
+
+impl Pattern {
+    /// Updates the patterns of the engine tracks.
+    /// - `patterns`: Patterns of the engine.
+    /// - `scale`: Scale the notes of melodic tracks are quantized to.
+    /// - `root`: Root note of the scale.
+    pub fn update_tracks(
+        &self,
+        patterns: &mut [dr_seq_engine::Pattern<16>; TRACKS],
+        scale: Scale,
+        root: i32,
+    ) {
+        for (t, pattern) in patterns.iter_mut().enumerate() {
+            // An enabled euclidean rhythm replaces the step states and the length,
+            // without changing the stored pattern.
+            let euclid = self.euclid(t);
+            let hits = euclid.map(|euclid| euclid.hits::<16>());
+
+            pattern.set_length(match euclid {
+                Some(euclid) => euclid.steps,
+                None => self.lengths[t].load(Ordering::Relaxed),
+            });
+
+            let mode = TrackMode::from(self.modes[t].load(Ordering::Relaxed));
+
+            for (s, step) in pattern.steps_mut().iter_mut().enumerate() {
+                let mut state = StepState::from(self.steps[t][s].load(Ordering::Relaxed));
+                if let Some(hits) = hits {
+                    state = euclid_step_state(state, hits[s]);
+                }
+                if state != StepState::Off {
+                    step.enable();
+
+                    let vel = match state {
+                        StepState::Accent => Velocity::Accent,
+                        StepState::Weak => Velocity::Weak,
+                        StepState::Ghost => Velocity::Ghost,
+                        StepState::Custom => Velocity::Custom(
+                            self.velocities[t][s].load(Ordering::Relaxed).min(127) as u8,
+                        ),
+                        _ => Velocity::Default,
+                    };
+
+                    step.set_event(Some(StepEvent::NoteOn {
+                        pitch: match mode {
+                            TrackMode::Drum => Pitch::Default,
+                            TrackMode::Melodic => {
+                                let note = self.notes[t][s].load(Ordering::Relaxed).min(127) as i32;
+                                Pitch::Custom(scale.quantize(note, root).clamp(0, 127))
+                            }
+                        },
+                        vel,
+                    }));
+
+                    // Layered notes are played with the same velocity as the step.
+                    let layers = self.layers[t][s].load(Ordering::Relaxed);
+                    for (l, layer_note) in self.layer_notes[t].iter().enumerate() {
+                        if layers & (1 << l) != 0 {
+                            let note = layer_note.load(Ordering::Relaxed).min(127) as i32;
+                            step.add_event(StepEvent::NoteOn {
+                                pitch: Pitch::Custom(note),
+                                vel,
+                            })
+                            .ok();
+                        }
+                    }
+                } else {
+                    step.disable();
+                }
+
+                let condition = self.conditions[t][s].load(Ordering::Relaxed);
+                step.set_condition(
+                    STEP_CONDITIONS
+                        .get(condition as usize)
+                        .copied()
+                        .unwrap_or_default(),
+                );
+                step.set_probability(
+                    self.probabilities[t][s].load(Ordering::Relaxed).min(100) as u8
+                );
+
+                let gate = self.gates[t][s].load(Ordering::Relaxed);
+                step.set_gate(STEP_GATES.get(gate as usize).copied().flatten());
+
+                step.set_nudge(self.nudges[t][s].load(Ordering::Relaxed));
+
+                let ratchets = self.ratchets[t][s].load(Ordering::Relaxed);
+                step.set_ratchets(ratchets.min(u8::MAX as u32) as u8);
+
+                let ramp = self.ramps[t][s].load(Ordering::Relaxed);
+                step.set_ramp(STEP_RAMPS.get(ramp as usize).copied().unwrap_or_default());
+
+                step.set_accent(self.accents[t][s].load(Ordering::Relaxed));
+            }
+        }
+    }
+
+    /// Returns the euclidean rhythm of a track, if it is enabled.
+    pub fn euclid(&self, track: usize) -> Option<Euclid> {
+        self.euclid_enabled[track].load(Ordering::Relaxed).then(|| {
+            Euclid::new(
+                self.euclid_pulses[track].load(Ordering::Relaxed),
+                self.euclid_steps[track].load(Ordering::Relaxed),
+                self.euclid_rotations[track].load(Ordering::Relaxed) as i32,
+            )
+        })
+    }
+
+    /// Resets all tracks to empty steps with default settings.
+    pub fn clear(&self) {
+        for t in 0..TRACKS {
+            for s in 0..16 {
+                self.steps[t][s].store(0, Ordering::Relaxed);
+                self.conditions[t][s].store(0, Ordering::Relaxed);
+                self.probabilities[t][s].store(100, Ordering::Relaxed);
+                self.gates[t][s].store(0, Ordering::Relaxed);
+                self.nudges[t][s].store(0, Ordering::Relaxed);
+                self.ratchets[t][s].store(1, Ordering::Relaxed);
+                self.ramps[t][s].store(0, Ordering::Relaxed);
+                self.velocities[t][s].store(100, Ordering::Relaxed);
+                self.notes[t][s].store(default_note(t), Ordering::Relaxed);
+                self.layers[t][s].store(0, Ordering::Relaxed);
+                self.accents[t][s].store(false, Ordering::Relaxed);
+            }
+            for l in 0..MAX_LAYERS {
+                self.layer_notes[t][l].store(default_layer_note(t, l), Ordering::Relaxed);
             }
+            self.lengths[t].store(16, Ordering::Relaxed);
+            self.modes[t].store(0, Ordering::Relaxed);
+            self.euclid_enabled[t].store(false, Ordering::Relaxed);
         }
     }
 
+    /// Stores all values of `other` into this pattern.
+    ///
+    /// Only atomic values are written, so this does not allocate and is safe
+    /// to be used on the audio thread.
+    pub fn copy_from(&self, other: &Pattern) {
+        copy_values(self.steps.as_flattened(), other.steps.as_flattened());
+        copy_values(&self.lengths, &other.lengths);
+        copy_values(
+            self.conditions.as_flattened(),
+            other.conditions.as_flattened(),
+        );
+        copy_values(
+            self.probabilities.as_flattened(),
+            other.probabilities.as_flattened(),
+        );
+        copy_values(self.gates.as_flattened(), other.gates.as_flattened());
+        copy_values(self.nudges.as_flattened(), other.nudges.as_flattened());
+        copy_values(self.ratchets.as_flattened(), other.ratchets.as_flattened());
+        copy_values(self.ramps.as_flattened(), other.ramps.as_flattened());
+        copy_values(
+            self.velocities.as_flattened(),
+            other.velocities.as_flattened(),
+        );
+        copy_values(&self.modes, &other.modes);
+        copy_values(self.notes.as_flattened(), other.notes.as_flattened());
+        copy_values(self.layers.as_flattened(), other.layers.as_flattened());
+        copy_values(
+            self.layer_notes.as_flattened(),
+            other.layer_notes.as_flattened(),
+        );
+        copy_values(&self.euclid_enabled, &other.euclid_enabled);
+        copy_values(&self.euclid_pulses, &other.euclid_pulses);
+        copy_values(&self.euclid_steps, &other.euclid_steps);
+        copy_values(&self.euclid_rotations, &other.euclid_rotations);
+        copy_values(self.accents.as_flattened(), other.accents.as_flattened());
+    }
+}
+
+/// Returns the state of a step when a euclidean rhythm is applied.
+///
+/// Steps with a hit keep their state if already enabled, otherwise they become normal steps.
+pub fn euclid_step_state(state: StepState, hit: bool) -> StepState {
+    match (hit, state) {
+        (false, _) => StepState::Off,
+        (true, StepState::Off) => StepState::Normal,
+        (true, state) => state,
+    }
+}
+
+/// Returns the default track lengths, which is the full 16 steps.
+fn default_lengths() -> [Arc<AtomicU32>; TRACKS] {
+    core::array::from_fn(|_| Arc::new(AtomicU32::new(16)))
+}
+
+/// Returns the default number of hits of the euclidean rhythms.
+fn default_euclid_pulses() -> [Arc<AtomicU32>; TRACKS] {
+    core::array::from_fn(|_| Arc::new(AtomicU32::new(4)))
+}
+
+/// Returns the default step probabilities, which is always playing.
+fn default_probabilities() -> [[Arc<AtomicU32>; 16]; TRACKS] {
+    filled_steps(100)
+}
+
+/// Returns the default step ratchets, which is a single hit.
+fn default_ratchets() -> [[Arc<AtomicU32>; 16]; TRACKS] {
+    filled_steps(1)
+}
+
+/// Returns the default step velocities.
+fn default_velocities() -> [[Arc<AtomicU32>; 16]; TRACKS] {
+    filled_steps(100)
+}
+
+/// Returns the default step notes, which is the note of the track.
+fn default_notes() -> [[Arc<AtomicU32>; 16]; TRACKS] {
+    core::array::from_fn(|track| {
+        core::array::from_fn(|_| Arc::new(AtomicU32::new(default_note(track))))
+    })
+}
+
+/// Returns the default layer notes, which are the notes following the note of the track.
+fn default_layer_notes() -> [[Arc<AtomicU32>; MAX_LAYERS]; TRACKS] {
+    core::array::from_fn(|track| {
+        core::array::from_fn(|layer| Arc::new(AtomicU32::new(default_layer_note(track, layer))))
+    })
+}
+
+/// Returns the default note number of a layer of a track.
+pub fn default_layer_note(track: usize, layer: usize) -> u32 {
+    (default_note(track) + layer as u32 + 1).min(127)
+}
+
+/// Returns the default note number of the steps of a track.
+pub fn default_note(track: usize) -> u32 {
+    TRACK_NOTES.get(track).copied().unwrap_or(60) as u32
+}
+
+/// Returns an array for all steps with the same initial `value`.
+fn filled_steps(value: u32) -> [[Arc<AtomicU32>; 16]; TRACKS] {
+    core::array::from_fn(|_| core::array::from_fn(|_| Arc::new(AtomicU32::new(value))))
+}
+
+/// Atomic values that can be copied from another instance.
+trait CopyValue {
+    /// Stores the value of `other`.
+    fn copy_value(&self, other: &Self);
+}
+
+impl CopyValue for AtomicU32 {
+    fn copy_value(&self, other: &Self) {
+        self.store(other.load(Ordering::Relaxed), Ordering::Relaxed)
+    }
+}
+
+impl CopyValue for AtomicI32 {
+    fn copy_value(&self, other: &Self) {
+        self.store(other.load(Ordering::Relaxed), Ordering::Relaxed)
+    }
+}
+
+impl CopyValue for AtomicBool {
+    fn copy_value(&self, other: &Self) {
+        self.store(other.load(Ordering::Relaxed), Ordering::Relaxed)
+    }
+}
+
+/// Stores the values from `new_values` into `values`.
+fn copy_values<T: CopyValue>(values: &[Arc<T>], new_values: &[Arc<T>]) {
+    for (value, new_value) in values.iter().zip(new_values) {
+        value.copy_value(new_value);
+    }
+}
+
+impl<'a> PersistentField<'a, Pattern> for Pattern {
+    fn set(&self, new_value: Pattern) {
+        self.copy_from(&new_value);
+    }
+
     fn map<F, R>(&self, f: F) -> R
     where
         F: Fn(&Pattern) -> R,
@@ -363,3 +1039,310 @@ impl<'a> PersistentField<'a, Pattern> for Pattern {
         f(self)
     }
 }
+
+impl Migrate for Pattern {
+    const MIGRATIONS: [Migration; 2] = [keep_layout, move_accent_track];
+}
+
+/// Number of tracks including the accent track in layouts of version 1.
+const V1_TRACKS: usize = 9;
+
+/// Fields of a pattern with a value per track in layouts of version 1.
+const V1_TRACK_FIELDS: [&str; 17] = [
+    "steps",
+    "lengths",
+    "conditions",
+    "probabilities",
+    "gates",
+    "nudges",
+    "ratchets",
+    "ramps",
+    "velocities",
+    "modes",
+    "notes",
+    "layers",
+    "layer_notes",
+    "euclid_enabled",
+    "euclid_pulses",
+    "euclid_steps",
+    "euclid_rotations",
+];
+
+/// Moves the accent track of a pattern from version 1, where it followed the 8 note tracks,
+/// to the end of all tracks. The note tracks added in between get their defaults.
+fn move_accent_track(state: &mut Value) {
+    for field in V1_TRACK_FIELDS {
+        if let Some(Value::Array(tracks)) = state.get_mut(field)
+            && tracks.len() == V1_TRACKS
+            && let Some(accent) = tracks.pop()
+        {
+            tracks.resize(TRACKS - 1, Value::Null);
+            tracks.push(accent);
+        }
+    }
+}
+
+/// Bank of stored patterns.
+///
+/// The pattern that is playing is edited in `AppParams::pattern` and written back
+/// into the bank when another pattern is selected.
+#[derive(Serialize, Deserialize)]
+pub struct PatternBank {
+    /// Number of the pattern that is playing, starting at 0.
+    #[serde(default)]
+    pub active: AtomicU32,
+
+    /// Stored patterns.
+    #[serde(default = "default_bank_patterns")]
+    pub patterns: [Pattern; BANK_SIZE],
+}
+
+impl Default for PatternBank {
+    fn default() -> Self {
+        Self {
+            active: AtomicU32::new(0),
+            patterns: default_bank_patterns(),
+        }
+    }
+}
+
+/// Returns empty patterns for the bank.
+fn default_bank_patterns() -> [Pattern; BANK_SIZE] {
+    core::array::from_fn(|_| Pattern::default())
+}
+
+impl<'a> PersistentField<'a, PatternBank> for PatternBank {
+    fn set(&self, new_value: PatternBank) {
+        self.active
+            .store(new_value.active.load(Ordering::Relaxed), Ordering::Relaxed);
+
+        for (pattern, new_pattern) in self.patterns.iter().zip(&new_value.patterns) {
+            pattern.copy_from(new_pattern);
+        }
+    }
+
+    fn map<F, R>(&self, f: F) -> R
+    where
+        F: Fn(&PatternBank) -> R,
+    {
+        f(self)
+    }
+}
+
+impl Migrate for PatternBank {
+    const MIGRATIONS: [Migration; 2] = [keep_layout, move_bank_accent_tracks];
+}
+
+/// Moves the accent tracks of the patterns of a bank from version 1.
+fn move_bank_accent_tracks(state: &mut Value) {
+    if let Some(Value::Array(patterns)) = state.get_mut("patterns") {
+        patterns.iter_mut().for_each(move_accent_track);
+    }
+}
+
+/// Chain of patterns for song mode.
+#[derive(Serialize, Deserialize)]
+pub struct ChainState {
+    /// Number of entries in use.
+    #[serde(default)]
+    pub length: Arc<AtomicU32>,
+
+    /// Patterns of the entries, starting at 0.
+    #[serde(default)]
+    pub patterns: [Arc<AtomicU32>; MAX_CHAIN_ENTRIES],
+
+    /// Number of bars the patterns of the entries are played.
+    #[serde(default = "default_chain_repeats")]
+    pub repeats: [Arc<AtomicU32>; MAX_CHAIN_ENTRIES],
+
+    /// Fill mode of the entries.
+    #[serde(default)]
+    pub fills: [Arc<AtomicBool>; MAX_CHAIN_ENTRIES],
+
+    /// Flag if the chain starts over after the last entry.
+    #[serde(default)]
+    pub looping: Arc<AtomicBool>,
+}
+
+impl Default for ChainState {
+    fn default() -> Self {
+        Self {
+            length: Default::default(),
+            patterns: Default::default(),
+            repeats: default_chain_repeats(),
+            fills: Default::default(),
+            looping: Default::default(),
+        }
+    }
+}
+
+impl ChainState {
+    /// Returns the chain for the engine.
+    pub fn to_chain(&self) -> Chain {
+        let mut chain = Chain::new();
+        let length = (self.length.load(Ordering::Relaxed) as usize).min(MAX_CHAIN_ENTRIES);
+
+        for n in 0..length {
+            chain
+                .push(ChainEntry {
+                    pattern: self.patterns[n].load(Ordering::Relaxed),
+                    repeats: self.repeats[n].load(Ordering::Relaxed),
+                    fill: self.fills[n].load(Ordering::Relaxed),
+                })
+                .ok();
+        }
+
+        chain.set_looping(self.looping.load(Ordering::Relaxed));
+
+        chain
+    }
+}
+
+/// Returns the default repeats of the chain entries, which is a single bar.
+fn default_chain_repeats() -> [Arc<AtomicU32>; MAX_CHAIN_ENTRIES] {
+    core::array::from_fn(|_| Arc::new(AtomicU32::new(1)))
+}
+
+impl<'a> PersistentField<'a, ChainState> for ChainState {
+    fn set(&self, new_value: ChainState) {
+        self.length
+            .store(new_value.length.load(Ordering::Relaxed), Ordering::Relaxed);
+        copy_values(&self.patterns, &new_value.patterns);
+        copy_values(&self.repeats, &new_value.repeats);
+        copy_values(&self.fills, &new_value.fills);
+        self.looping
+            .store(new_value.looping.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    fn map<F, R>(&self, f: F) -> R
+    where
+        F: Fn(&ChainState) -> R,
+    {
+        f(self)
+    }
+}
+
+impl Migrate for ChainState {
+    const MIGRATIONS: [Migration; 2] = [keep_layout, keep_layout];
+}
+
+/// Notes and MIDI channels used by the tracks.
+#[derive(Serialize, Deserialize)]
+pub struct NoteMap {
+    /// Note numbers of the tracks, except the accent track.
+    #[serde(default = "default_track_notes")]
+    pub notes: [Arc<AtomicU32>; TRACKS - 1],
+
+    /// Output channels of the tracks in range 0-15.
+    #[serde(default)]
+    pub channels: [Arc<AtomicU32>; TRACKS - 1],
+}
+
+impl Default for NoteMap {
+    fn default() -> Self {
+        Self {
+            notes: default_track_notes(),
+            channels: Default::default(),
+        }
+    }
+}
+
+impl NoteMap {
+    /// Returns the note number of a track.
+    pub fn note(&self, track: usize) -> u8 {
+        self.notes
+            .get(track)
+            .map_or(0, |note| note.load(Ordering::Relaxed).min(127) as u8)
+    }
+
+    /// Returns the note numbers of all tracks.
+    pub fn notes(&self) -> [u8; TRACKS - 1] {
+        core::array::from_fn(|track| self.note(track))
+    }
+
+    /// Returns the output channel of a track in range 0-15.
+    pub fn channel(&self, track: usize) -> u8 {
+        self.channels
+            .get(track)
+            .map_or(0, |channel| channel.load(Ordering::Relaxed).min(15) as u8)
+    }
+}
+
+/// Returns the default notes of the tracks.
+fn default_track_notes() -> [Arc<AtomicU32>; TRACKS - 1] {
+    TRACK_NOTES.map(|note| Arc::new(AtomicU32::new(note as u32)))
+}
+
+impl<'a> PersistentField<'a, NoteMap> for NoteMap {
+    fn set(&self, new_value: NoteMap) {
+        copy_values(&self.notes, &new_value.notes);
+        copy_values(&self.channels, &new_value.channels);
+    }
+
+    fn map<F, R>(&self, f: F) -> R
+    where
+        F: Fn(&NoteMap) -> R,
+    {
+        f(self)
+    }
+}
+
+impl Migrate for NoteMap {
+    const MIGRATIONS: [Migration; 2] = [keep_layout, keep_layout];
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::state::load;
+
+    /// Returns an unversioned pattern with the accent track following the 8 note tracks.
+    ///
+    /// The steps of note track `t` are in state `t % 4`, the accent steps in state 1.
+    fn v0_pattern() -> Value {
+        let mut steps: Vec<_> = (0..V1_TRACKS - 1).map(|t| [t % 4; 16]).collect();
+        steps.push([1; 16]);
+
+        json!({ "steps": steps, "lengths": [16, 16, 16, 16, 16, 16, 16, 16, 12] })
+    }
+
+    /// Checks the tracks of a pattern loaded from `v0_pattern()`.
+    fn assert_accent_track_moved(pattern: &Pattern) {
+        let states = |t: usize| {
+            pattern.steps[t]
+                .each_ref()
+                .map(|s| s.load(Ordering::Relaxed))
+        };
+        let length = |t: usize| pattern.lengths[t].load(Ordering::Relaxed);
+
+        for t in 0..V1_TRACKS - 1 {
+            assert_eq!(states(t), [t as u32 % 4; 16], "track {t}");
+        }
+        for t in V1_TRACKS - 1..ACCENT_TRACK as usize {
+            assert_eq!(states(t), [0; 16], "track {t}");
+            assert_eq!(length(t), 16, "track {t}");
+        }
+        assert_eq!(states(ACCENT_TRACK as usize), [1; 16]);
+        assert_eq!(length(ACCENT_TRACK as usize), 12);
+    }
+
+    #[test]
+    fn unversioned_pattern_moves_accent_track() {
+        let pattern = load::<Pattern>(v0_pattern()).unwrap();
+        assert_accent_track_moved(&pattern);
+    }
+
+    #[test]
+    fn unversioned_bank_moves_accent_tracks() {
+        let bank =
+            load::<PatternBank>(json!({ "patterns": [v0_pattern(), v0_pattern()] })).unwrap();
+        assert_accent_track_moved(&bank.patterns[0]);
+        assert_accent_track_moved(&bank.patterns[1]);
+        assert_eq!(
+            bank.patterns[2].steps[ACCENT_TRACK as usize][0].load(Ordering::Relaxed),
+            0
+        );
+    }
+}
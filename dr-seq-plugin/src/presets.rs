@@ -11,21 +11,29 @@
 //!     - o / O: open
 //!     - h / H: pedal
 
-use std::sync::Arc;
 use std::sync::atomic::Ordering;
 
-use crate::{
-    config::TRACKS,
-    params::{AppParams, StepState},
-};
+use crate::params::{AppParams, StepState};
 
 /// Definition of a preset pattern.
 pub struct PresetPattern {
+    /// Name shown in the library.
+    pub name: &'static str,
+
+    /// Category the preset is listed in.
+    pub category: &'static str,
+
+    /// Keywords describing the preset.
+    pub tags: &'static [&'static str],
+
     /// Steps
     pub steps: [&'static str; 6],
 }
 
 pub static PRESET_PATTERN_0: PresetPattern = PresetPattern {
+    name: "Four on the Floor",
+    category: "House",
+    tags: &["4/4", "basic"],
     steps: [
         "X___X___X___X___",
         "____X_______X___",
@@ -37,6 +45,9 @@ pub static PRESET_PATTERN_0: PresetPattern = PresetPattern {
 };
 
 pub static PRESET_PATTERN_1: PresetPattern = PresetPattern {
+    name: "Open Offbeat",
+    category: "House",
+    tags: &["4/4", "open hihat"],
     steps: [
         "X___X___X___X___",
         "____X_______X___",
@@ -48,6 +59,9 @@ pub static PRESET_PATTERN_1: PresetPattern = PresetPattern {
 };
 
 pub static PRESET_PATTERN_2: PresetPattern = PresetPattern {
+    name: "Pedal Groove",
+    category: "House",
+    tags: &["4/4", "pedal hihat"],
     steps: [
         "X___X___X___X___",
         "____X_______X___",
@@ -59,6 +73,9 @@ pub static PRESET_PATTERN_2: PresetPattern = PresetPattern {
 };
 
 pub static PRESET_PATTERN_3: PresetPattern = PresetPattern {
+    name: "Sixteenths",
+    category: "House",
+    tags: &["4/4", "16th hihat"],
     steps: [
         "X___X___X___X___",
         "____X_______X___",
@@ -70,6 +87,9 @@ pub static PRESET_PATTERN_3: PresetPattern = PresetPattern {
 };
 
 pub static PRESET_PATTERN_4: PresetPattern = PresetPattern {
+    name: "Wood Shuffle",
+    category: "House",
+    tags: &["4/4", "percussion"],
     steps: [
         "X___X___X___X___",
         "____X_______X___",
@@ -81,6 +101,9 @@ pub static PRESET_PATTERN_4: PresetPattern = PresetPattern {
 };
 
 pub static PRESET_PATTERN_5: PresetPattern = PresetPattern {
+    name: "Broken Beat",
+    category: "Breaks",
+    tags: &["syncopated", "percussion"],
     steps: [
         "X__X__X____X_XX_",
         "____X__X_X__X___",
@@ -93,6 +116,9 @@ pub static PRESET_PATTERN_5: PresetPattern = PresetPattern {
 
 /// DnB main.
 pub static PRESET_PATTERN_6: PresetPattern = PresetPattern {
+    name: "DnB Main",
+    category: "DnB",
+    tags: &["breakbeat"],
     steps: [
         "X_________X_____",
         "____X__x_x__X___",
@@ -105,6 +131,9 @@ pub static PRESET_PATTERN_6: PresetPattern = PresetPattern {
 
 /// DnB variation.
 pub static PRESET_PATTERN_7: PresetPattern = PresetPattern {
+    name: "DnB Variation",
+    category: "DnB",
+    tags: &["breakbeat"],
     steps: [
         "X_____X_________",
         "____X_____X_____",
@@ -115,25 +144,26 @@ pub static PRESET_PATTERN_7: PresetPattern = PresetPattern {
     ],
 };
 
-/// Loads a preset into the parameters.
-pub fn load_preset(preset_no: u32, params: Arc<AppParams>) {
-    let preset = match preset_no {
-        1 => &PRESET_PATTERN_1,
-        2 => &PRESET_PATTERN_2,
-        3 => &PRESET_PATTERN_3,
-        4 => &PRESET_PATTERN_4,
-        5 => &PRESET_PATTERN_5,
-        6 => &PRESET_PATTERN_6,
-        7 => &PRESET_PATTERN_7,
-        _ => &PRESET_PATTERN_0,
-    };
-
-    // Clear pattern.
-    for t in 0..TRACKS {
-        for s in 0..16 {
-            params.pattern.steps[t][s].store(0, Ordering::Relaxed);
-        }
-    }
+/// Factory presets in the order they are listed in the library.
+pub static FACTORY_PRESETS: [&PresetPattern; 8] = [
+    &PRESET_PATTERN_0,
+    &PRESET_PATTERN_1,
+    &PRESET_PATTERN_2,
+    &PRESET_PATTERN_3,
+    &PRESET_PATTERN_4,
+    &PRESET_PATTERN_5,
+    &PRESET_PATTERN_6,
+    &PRESET_PATTERN_7,
+];
+
+/// Loads a factory preset into the parameters.
+pub fn load_preset(preset_no: usize, params: &AppParams) {
+    let preset = FACTORY_PRESETS
+        .get(preset_no)
+        .copied()
+        .unwrap_or(&PRESET_PATTERN_0);
+
+    params.pattern.clear();
 
     for (t, steps) in preset.steps.iter().enumerate() {
         for (s, step) in steps.chars().enumerate() {
@@ -0,0 +1,250 @@
+//! Versioned persistent state.
+//!
+//! Persisted fields are wrapped into an envelope with the version of their layout:
+//!
+//! ```json
+//! { "version": 2, "state": { ... } }
+//! ```
+//!
+//! When loading, the state is converted step by step from its version to the current one
+//! by the migrations of its type. Afterwards, it is merged onto the default state, so fields
+//! added since and arrays grown in capacity get their default values, while values exceeding
+//! a reduced capacity are dropped.
+//!
+//! States saved before the envelope was introduced are treated as version 0.
+
+use std::ops::Deref;
+
+use nice_plug::params::persist::PersistentField;
+use serde::de::{DeserializeOwned, Error};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+/// Version of the current layout of the persisted state.
+///
+/// Increment this when the layout changes in a way that can't be handled by merging onto the
+/// default state, e.g. when values are renamed, moved or change their meaning, and add the
+/// migrations from the previous version.
+pub const STATE_VERSION: u32 = 2;
+
+/// Function converting a serialized state from one version into the next one.
+pub type Migration = fn(&mut Value);
+
+/// Type that can be persisted with a version.
+pub trait Migrate: Serialize + DeserializeOwned + Default {
+    /// Migrations of the serialized state, indexed by the version they convert from.
+    const MIGRATIONS: [Migration; STATE_VERSION as usize];
+}
+
+/// Migration of states whose layout is unchanged in the next version.
+pub fn keep_layout(_state: &mut Value) {}
+
+/// Persisted value wrapped into an envelope with the version of its layout.
+#[derive(Debug, Default)]
+pub struct Versioned<T>(T);
+
+impl<T> Deref for Versioned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Envelope of a serialized state.
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    /// Version of the layout.
+    version: u32,
+
+    /// The state itself.
+    state: &'a T,
+}
+
+impl<T: Serialize> Serialize for Versioned<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Envelope {
+            version: STATE_VERSION,
+            state: &self.0,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T: Migrate> Deserialize<'de> for Versioned<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+
+        load(value).map(Self).map_err(D::Error::custom)
+    }
+}
+
+impl<'a, T: PersistentField<'a, T>> PersistentField<'a, Versioned<T>> for Versioned<T> {
+    fn set(&self, new_value: Versioned<T>) {
+        self.0.set(new_value.0);
+    }
+
+    fn map<F, R>(&self, f: F) -> R
+    where
+        F: Fn(&Versioned<T>) -> R,
+    {
+        f(self)
+    }
+}
+
+/// Returns a state loaded from its serialized form, with or without envelope.
+///
+/// States of a newer version than supported are loaded without migrations,
+/// keeping all values that fit into the current layout.
+pub fn load<T: Migrate>(value: Value) -> Result<T, serde_json::Error> {
+    let (version, mut state) = match value {
+        Value::Object(mut envelope)
+            if envelope.contains_key("version") && envelope.contains_key("state") =>
+        {
+            let version = envelope
+                .get("version")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| serde_json::Error::custom("invalid state version"))?;
+            let state = envelope.remove("state").unwrap_or_default();
+            (version, state)
+        }
+        state => (0, state),
+    };
+
+    for migration in T::MIGRATIONS.iter().skip(version as usize) {
+        migration(&mut state);
+    }
+
+    let mut merged = serde_json::to_value(T::default())?;
+    merge(&mut merged, state);
+
+    serde_json::from_value(merged)
+}
+
+/// Merges the values of `state` onto `target`.
+///
+/// Object fields and array elements missing in `state` keep the values of `target`,
+/// values that don't exist in `target` or have a different type are ignored.
+fn merge(target: &mut Value, state: Value) {
+    match (target, state) {
+        (Value::Object(target), Value::Object(state)) => {
+            for (key, value) in state {
+                if let Some(target) = target.get_mut(&key) {
+                    merge(target, value);
+                }
+            }
+        }
+        (Value::Array(target), Value::Array(state)) => {
+            for (target, value) in target.iter_mut().zip(state) {
+                merge(target, value);
+            }
+        }
+        (target @ Value::Number(_), state @ Value::Number(_))
+        | (target @ Value::Bool(_), state @ Value::Bool(_))
+        | (target @ Value::String(_), state @ Value::String(_)) => {
+            *target = state;
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// State counting the migrations applied to it.
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct TestState {
+        count: u32,
+        flags: [bool; 3],
+        name: String,
+        migrations: u32,
+    }
+
+    impl Migrate for TestState {
+        const MIGRATIONS: [Migration; STATE_VERSION as usize] =
+            [count_migration; STATE_VERSION as usize];
+    }
+
+    /// Increments the number of migrations of the state.
+    fn count_migration(state: &mut Value) {
+        if let Some(state) = state.as_object_mut() {
+            let migrations = state.get("migrations").and_then(Value::as_u64);
+            state.insert("migrations".into(), (migrations.unwrap_or(0) + 1).into());
+        }
+    }
+
+    #[test]
+    fn unversioned_state_is_migrated_from_version_0() {
+        let state: TestState = load(json!({ "count": 3, "flags": [true] })).unwrap();
+        assert_eq!(
+            state,
+            TestState {
+                count: 3,
+                flags: [true, false, false],
+                migrations: STATE_VERSION,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn current_version_is_not_migrated() {
+        let value = json!({ "version": STATE_VERSION, "state": { "count": 4 } });
+        let state: TestState = load(value).unwrap();
+        assert_eq!(state.count, 4);
+        assert_eq!(state.migrations, 0);
+
+        let value = json!({ "version": 1, "state": {} });
+        let state: TestState = load(value).unwrap();
+        assert_eq!(state.migrations, STATE_VERSION - 1);
+    }
+
+    #[test]
+    fn newer_version_keeps_known_values() {
+        let value = json!({
+            "version": STATE_VERSION + 1,
+            "state": { "count": 5, "flags": [true, true, true, true], "added": 1 },
+        });
+        let state: TestState = load(value).unwrap();
+        assert_eq!(
+            state,
+            TestState {
+                count: 5,
+                flags: [true; 3],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn mismatched_value_keeps_its_default() {
+        let value = json!({
+            "version": STATE_VERSION,
+            "state": { "count": "many", "flags": false, "name": "kit" },
+        });
+        let state: TestState = load(value).unwrap();
+        assert_eq!(
+            state,
+            TestState {
+                name: "kit".into(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn versioned_round_trip() {
+        let state = Versioned(TestState {
+            count: 7,
+            ..Default::default()
+        });
+        let text = serde_json::to_string(&state).unwrap();
+        assert!(text.starts_with(&format!(r#"{{"version":{STATE_VERSION},"state":"#)));
+
+        let loaded: Versioned<TestState> = serde_json::from_str(&text).unwrap();
+        assert_eq!(*loaded, *state);
+    }
+}